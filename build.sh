@@ -5,6 +5,7 @@
 #cross compliation
 #cargo build --target=x86_64-unknown-linux-musl --workspace --release
 
+#add --features fixed-point to both builds for fixed-point physics
 cargo build --release
 cd utils
 wasm-pack build --target web
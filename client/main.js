@@ -1,7 +1,36 @@
-import init, { Trajectory, UpdateType, UpdateTypeWrapper, getbody, num_bodies, get_shop_item, num_shop_items, dome_radius, World } from './pkg/utils.js';
+import init, { Trajectory, UpdateType, UpdateTypeWrapper, getbody, body_outline, num_bodies, get_shop_item, num_shop_items, dome_radius, max_dome_radius, body_positions, load_map, hash_version, max_fuel, binary_protocol, encode_message, decode_message, World } from './pkg/utils.js';
 async function runAll(){
 	await init();
 
+	//the map has to be loaded before anything touches the physics
+	const map_hash = await (async () => {
+		const response = await fetch(`${window.location.origin}/map`);
+		if (!response.ok){
+			console.error("Can't load map");
+			return null;
+		}
+		const hash = load_map(await response.text());
+		if (hash === undefined){
+			console.error("Received invalid map");
+			return null;
+		}
+		return hash;
+	})();
+
+	//prices and the loot radius, the server can be set up with others than the shop was built with
+	const gameplay = await (async () => {
+		const response = await fetch(`${window.location.origin}/gameplay`);
+		if (!response.ok){
+			console.error("Can't load gameplay settings");
+			return {};
+		}
+		return await response.json();
+	})();
+	const prices = {"AddBoost": gameplay.boost_price, "Refuel": gameplay.refuel_price};
+	//a bit inside the server's radius, it checks where the ship was when the claim arrived
+	const claim_radius = (gameplay.loot_radius ?? 60) * 2/3;
+	const pistol_damage = gameplay.pistol_damage ?? 25;
+
 	// Get the modal
 	const joinmodal = document.getElementById("join-modal");
 	const form = joinmodal.querySelector('form');
@@ -112,18 +141,20 @@ async function runAll(){
 		const items_div = document.getElementById("shop-items");
 		for (let i = 0;i < num_shop_items(); ++i){
 			let item = get_shop_item(i);
-			console.log(item.cost);
+			const utype = item.update_type();
+			const cost = (utype === undefined ? undefined : prices[UpdateType[utype]]) ?? item.cost;
+			console.log(cost);
 			console.log(item.display_name());
-			const [item_card, buy_btn] = createItemCard(item.image_src(), item.display_name(), item.display_name(), "description", item.cost);
+			const [item_card, buy_btn] = createItemCard(item.image_src(), item.display_name(), item.display_name(), "description", cost);
 			items_div.appendChild(item_card);
 			buy_btn.addEventListener('click', () => {
 				console.log(`buy ${item.display_name()} ${item.id}`);
-				if (gameState[public_id].p.cash < item.cost){
+				if (utype === undefined || gameState[public_id].p.cash < cost){
 					return;
 				}
-				gameState[public_id].p.cash -= item.cost;
+				gameState[public_id].p.cash -= cost;
 				cash_text.text = gameState[public_id].p.cash;
-				perform_update("AddBoost");
+				perform_update(UpdateType[utype]);
 				update_player_sprite(public_id);
 			});
 		}
@@ -136,9 +167,12 @@ async function runAll(){
 
 		var gameState = {};
 		var worldLoot = {};
+		var projectiles = {}; //grenades and blaster bolts in flight
+		var grenade_held = false; //the server was told the trigger is pressed
 		var emissions = [];
 		var bodies = [];
 		var socket = null;
+		var binary = false; //whether the server agreed to the binary protocol
 		var opened = false;
 		var public_id = null;
 
@@ -146,6 +180,7 @@ async function runAll(){
 		const keyright = "d";
 		const keyup = "w";
 		const keyshoot = " ";
+		const keyslots = ["1", "2", "3"]; //pistol, grenade, blaster
 		const keyshop = "escape";
 		const keyzoomin = "arrowup";
 		const keyzoomout = "arrowdown";
@@ -155,6 +190,7 @@ async function runAll(){
 			[keyright]: false,
 			[keyup]: false,
 			[keyshoot]: false,
+			...Object.fromEntries(keyslots.map(key => [key, false])),
 			[keyshop]: false,
 			[keyzoomin]: false,
 			[keyzoomout]: false,
@@ -165,11 +201,15 @@ async function runAll(){
 		const ACCELERATION = 200.0;
 		const G = 2000.0;
 		const TIMESTEP_FPS = 8;
+		const MAX_FUEL = max_fuel();
 
 		const bullet_distance = 500;
 		const player_speed = 200;
 		const player_radius = 25;
 		const fadeRate = 2;
+		const prediction_steps = 100; //how far ahead the orbit line goes, in steps of 100ms
+		const grenade_radius = 8; //only drawn this big, touches bodies like a ship
+		const bolt_radius = 4;
 		const rotation_speed = PI;
 
 		const background_scale = 4;
@@ -185,6 +225,9 @@ async function runAll(){
 		var pingInterval = null;
 		var lastPing = 0;
 		var current_rtt = null;
+		var loaded = false; //whether the first snapshot was received
+		const departed = new Set(); //players seen dying that the server doesn't know about yet
+		const correcting = new Map(); //ships whose state was asked for, and when
 		var clocks_delta = 0; //estimated difference between client/server clocks
 
 		PIXI.settings.SPRITE_MAX_TEXTURES = 1; //supposidely improves performance
@@ -245,41 +288,37 @@ async function runAll(){
 		const players_container = new PIXI.Container();
 		const loot_container = new PIXI.Container();
 		const bullets_container = new PIXI.Container();
+		const projectiles_container = new PIXI.Container();
 		const bodies_container = new PIXI.Container();
-		const blobs_container = new PIXI.Container();
 
 		//higher zindex makes it appear on top
 		bullets_container.zIndex = 1;
+		projectiles_container.zIndex = 1;
 		players_container.zIndex = 2;
 		bodies_container.zIndex = 3;
 		loot_container.zIndex = 4;
-		blobs_container.zIndex = 5;
-
-		let beizer = new PIXI.Graphics();
-		app.stage.addChild(beizer);
-		beizer.beginFill(0xff0000, 0.5);
-		beizer.moveTo(50, 50);
-		beizer.zIndex = 5
-		beizer.quadraticCurveTo(100, 100, 150, 50);
-		beizer.quadraticCurveTo(200, 0, 250, 50);
-		beizer.quadraticCurveTo(300, 100, 350, 50);
-		beizer.quadraticCurveTo(400, -200, 450, 100); // Updated this line
-		beizer.quadraticCurveTo(450, 100, 100, 100); // Updated this line
-		beizer.endFill();
-
-		blobs_container.addChild(beizer);
+
 
 		const worldMask = new PIXI.Graphics();
 		worldMask.beginFill(0xffffff);
-		worldMask.drawCircle(0,0,dome_radius());
+		worldMask.drawCircle(0,0,max_dome_radius());
 		worldMask.endFill();
 
 		world.addChild(players_container);
 		world.addChild(loot_container);
 		world.addChild(bullets_container);
+		world.addChild(projectiles_container);
 		world.addChild(bodies_container);
-		world.addChild(blobs_container);
 		world.addChild(worldMask);
+
+		//the wall of the dome, which may shrink during a round
+		const dome_line = new PIXI.Graphics();
+		world.addChild(dome_line);
+		var drawn_dome_radius = null;
+
+		//where the own ship goes if nothing changes
+		const prediction_line = new PIXI.Graphics();
+		world.addChild(prediction_line);
 		world.mask = worldMask;
 
 		var backgrounds = {};
@@ -312,6 +351,14 @@ async function runAll(){
 		);
 		app.stage.addChild(heart_sprite);
 
+		//Add the fuel bar above the healthbar
+		var fuelbar = new PIXI.Sprite(PIXI.Texture.WHITE);
+		fuelbar.width = app.screen.width * healthbar_maxwidth;
+		fuelbar.height = 10;
+		fuelbar.tint = 0xffaa00;
+		fuelbar.position.set(app.screen.width*0.01, app.screen.height*0.98 - 35);
+		app.stage.addChild(fuelbar);
+
 		//add the coords text
 		var coords_text = new PIXI.Text("x: -, y: -", { fontFamily: "Arial", fontSize: 18, fill: 0x88ff88 });
 		coords_text.anchor.set(0.5);
@@ -330,6 +377,12 @@ async function runAll(){
 		latency_text.position.set(app.screen.width * 0.93, app.screen.height*0.02 + 40);
 		app.stage.addChild(latency_text);
 
+		//add the crash warning
+		var crash_text = new PIXI.Text("", { fontFamily: "Arial", fontSize: 22, fill: 0xff4444 });
+		crash_text.anchor.set(0.5);
+		crash_text.position.set(app.screen.width * 0.5, app.screen.height*0.1);
+		app.stage.addChild(crash_text);
+
 		//add the cash text
 		var cash_text = new PIXI.Text("?", {
 			fill: "#e5a50a",
@@ -382,19 +435,27 @@ async function runAll(){
 			.then(response => response.json())
 			.then(result => {
 				public_id = result['public'];
-				socket = new WebSocket(`wss://${window.location.hostname}/ws/`+result['private']);
-				//socket = new WebSocket(`ws://${window.location.host}/ws/`+result['private']);
+				//binary is preferred, JSON is there for servers that don't speak this version of it
+				const protocols = [binary_protocol(), "json"];
+				socket = new WebSocket(`wss://${window.location.hostname}/ws/`+result['private']+`?map=${map_hash}&hashv=${hash_version()}`, protocols);
+				//socket = new WebSocket(`ws://${window.location.host}/ws/`+result['private']+`?map=${map_hash}&hashv=${hash_version()}`, protocols);
+				socket.binaryType = "arraybuffer";
 				socket.onmessage = recvFn;
 				socket.onopen = () => {
 					opened=true;
+					binary = socket.protocol == binary_protocol();
 					const pingFn = () => { //TODO: instead of using ping, use other things to measure latency
-						socket.send(JSON.stringify({"t": "Ping"}));
+						send({"t": "Ping"});
 						lastPing = local_time();
 						setTimeout(pingFn, 5000+(Math.random()*5000));
 					}
 					pingFn();
 				};
 				socket.onclose = (event)=>{
+					if (!opened){
+						//the server refuses the upgrade on a map or hash version mismatch
+						console.error(`Connection refused, incompatible client (hash version ${hash_version()})`);
+					}
 					opened=false;
 					clearInterval(pingInterval);
 				}
@@ -464,6 +525,18 @@ async function runAll(){
 			return body;
 		}
 
+		//there is no image for fuel yet, draw a canister instead
+		const fuel_texture = (() => {
+			const canister = new PIXI.Graphics();
+			canister.beginFill(0xffaa00);
+			canister.drawRoundedRect(0, 0, 24, 32, 4);
+			canister.endFill();
+			canister.beginFill(0x101510);
+			canister.drawRect(8, 6, 8, 20);
+			canister.endFill();
+			return app.renderer.generateTexture(canister);
+		})();
+
 		const getLootSprite = function(lootObj){
 			var loot_obj = new PIXI.Container();
 			loot_obj.position.set(lootObj.x, lootObj.y);
@@ -472,6 +545,7 @@ async function runAll(){
 				"PistolAmmo": pistol_ammo_texture,
 				"SpeedBoost": speed_boost_texture,
 				"Health": heart_texture,
+				"Fuel": fuel_texture,
 			}[typeof lootObj.loot === "string" ? lootObj.loot:Object.keys(lootObj.loot)[0]]);
 			loot_texture.anchor.set(0.5);
 			loot_obj.addChild(loot_texture);
@@ -484,7 +558,70 @@ async function runAll(){
 			healthbar.tint = Math.round(0xff * prcnt) << 8 | Math.round((1-prcnt) * 0xff) << 16;
 		}
 
+		const update_fuelbar = function(fuelvalue){
+			fuelbar.width = app.screen.width * healthbar_maxwidth * fuelvalue / MAX_FUEL;
+		}
+
+		//The server pushes the world every tick, the first one sets it up. Snapshots with a base
+		//only list what changed since then, the applied ones are acknowledged so the server knows
+		//what to build on. Until one is, they list everything.
 		const handle_gamestate = function(state){
+			if (current_rtt == null)
+				return; //nothing can be advanced before the clocks are synced
+			if (state.base == null && loaded)
+				merge_gamestate(state);
+			else if (state.base == null)
+				load_gamestate(state);
+			else if (loaded)
+				apply_delta(state);
+			else
+				return;
+			send({"t": "AckSnapshot", "c": {"sequence": state.sequence}});
+		}
+
+		//later ones are where everyone was a moment ago, the ships are corrected to them
+		const merge_gamestate = function(state){
+			apply_delta(state);
+			const listed = new Set(state.players.map(p => p.id));
+			Object.keys(gameState).forEach(pid => {
+				if (pid != public_id && !listed.has(pid))
+					handle_playerleave(pid);
+			});
+			Object.keys(worldLoot).forEach(loot_uuid => {
+				if (!(loot_uuid in state.loot))
+					forget_loot(loot_uuid);
+			});
+		}
+
+		const apply_delta = function(state){
+			state.players.forEach((p) => {
+				if (departed.has(p.id))
+					return; //already seen dying, the server is a bit behind
+				if (!(p.id in gameState)) {
+					if (p.id != public_id)
+						handle_playerjoin(p);
+					return;
+				}
+				const known = gameState[p.id].p.trajectory;
+				const snapshot = new Trajectory(p.trajectory);
+				//a ship drawn further back than the snapshot gets there with the updates instead
+				if (snapshot.time <= known.time)
+					known.correct(p.trajectory);
+				snapshot.free();
+			});
+			state.gone.forEach(pid => {
+				if (pid != public_id)
+					handle_playerleave(pid);
+			});
+			Object.entries(state.loot).forEach(([loot_uuid, lootObj]) => {
+				if (!(loot_uuid in worldLoot))
+					summon_loot(loot_uuid, lootObj);
+			});
+			state.taken.forEach(forget_loot);
+		}
+
+		const load_gamestate = function(state){
+			loaded = true;
 			//remove all other sprites
 			for (var i = world.children.length - 1; i >= 0; i--)
 				players_container.removeChild(world.children[i]);
@@ -522,19 +659,34 @@ async function runAll(){
 				summon_loot(loot_uuid, lootObj);
 			});
 
+			bodies_container.removeChildren();
 			for (let i = 0;i < num_bodies();++i){
 				const body = getbody(i);
 				var body_obj = new PIXI.Container();
-				let circle = new PIXI.Graphics();
-				circle.beginFill(0xffffff);
-				circle.drawCircle(0, 0, body.radius);//body.radius
-				circle.endFill();
-				let circleTexture = app.renderer.generateTexture(circle);
-				circle = new PIXI.Sprite(circleTexture);
-				circle.anchor.set(0.5);
+				const outline = body_outline(i);
+				if (outline.length > 0) {
+					//the same curves ships crash into, drawn around the body's position
+					const shape = new PIXI.Graphics();
+					shape.beginFill(0xffffff);
+					shape.moveTo(outline[0], outline[1]);
+					for (let j = 0; j < outline.length; j += 4) {
+						const next = (j + 4) % outline.length;
+						shape.quadraticCurveTo(outline[j+2], outline[j+3], outline[next], outline[next+1]);
+					}
+					shape.endFill();
+					body_obj.addChild(shape);
+				} else {
+					let circle = new PIXI.Graphics();
+					circle.beginFill(0xffffff);
+					circle.drawCircle(0, 0, body.radius);//body.radius
+					circle.endFill();
+					let circleTexture = app.renderer.generateTexture(circle);
+					circle = new PIXI.Sprite(circleTexture);
+					circle.anchor.set(0.5);
+					body_obj.addChild(circle);
+				}
 
 				body_obj.position.set(body.pos.x, body.pos.y);
-				body_obj.addChild(circle);
 				bodies_container.addChild(body_obj);
 				bodies.push(body);
 			}
@@ -554,19 +706,26 @@ async function runAll(){
 			if (broadcaster == public_id)
 				return;
 
-			if (content["time"] < Number(gameState[broadcaster].p.trajectory.time)) {
-				console.error(`update is in the past`);
-			}
+			//a late update rewinds the ship and replays what happened since
 			const wrapper = new UpdateTypeWrapper(UpdateType[content.change.utype], content.change.value);
 			if (!gameState[broadcaster].p.trajectory.insert_update(
 				wrapper,
 				content["at"],
 				BigInt(content["time"])
 			)) {
-				console.error("Update insertion failed");
+				request_correction(broadcaster);
 			}
 		}
 
+		//asks for a ship's state once, and again only if no answer came within a second
+		const request_correction = function(pid){
+			const asked = correcting.get(pid);
+			if (asked !== undefined && local_time() - asked < 1000)
+				return;
+			correcting.set(pid, local_time());
+			send({"t": "Correct", "c": pid});
+		}
+
 		const change_propulsion_emitter = (pid, is_emitting) => {
 			if (gameState[pid].emitter && gameState[pid].emitter.emit == is_emitting){ //do nothing if already in that state
 				return;
@@ -652,23 +811,26 @@ async function runAll(){
 			if (content.shooter == public_id)
 				return;
 
-			spawn_gunshot(content.shooter);
+			//only players in view are known, the shooter or the victim may be further away
+			if (content.shooter in gameState)
+				spawn_gunshot(content.shooter);
 
-			if (!content.victim)
+			if (!content.victim || !(content.victim.id in gameState))
 				return;
 
 			const shallow_copy = content.victim.id == public_id ? world.pivot:gameState[content.victim.id].graphics;
-			const line_rotation = gameState[content.shooter].child.rotation;
+			const line_rotation = content.shooter in gameState ? gameState[content.shooter].child.rotation:0;
 
 			spawn_hit_emitter(shallow_copy.x, shallow_copy.y, line_rotation);
 
-			gameState[content.victim.id].p.trajectory.apply_change(new UpdateTypeWrapper(UpdateType["Bullet"], 25));
+			gameState[content.victim.id].p.trajectory.record_change(new UpdateTypeWrapper(UpdateType["Bullet"], pistol_damage));
 			if (content.victim.id == public_id) { //if you got hit
 				update_healthbar(gameState[content.victim.id].p.trajectory.health);
 			}
 		}
 
 		const handle_playerjoin = function(content){
+			departed.delete(content.id);
 			content.trajectory = new Trajectory(content.trajectory);
 			if (content.id == public_id){ //this happens when spawning
 				change_propulsion_emitter(public_id, content.trajectory.propelling);
@@ -706,42 +868,73 @@ async function runAll(){
 		}
 
 		const handle_playerleave = function(public_id){
+			if (!(public_id in gameState))
+				return;
 			players_container.removeChild(gameState[public_id].graphics);
 			delete gameState[public_id];
+			correcting.delete(public_id);
+		}
+
+		//the server only tells about what is within view, these came into it
+		const handle_enterview = function(content){
+			if (!loaded)
+				return; //the first snapshot has them
+			content.players.filter(p => !departed.has(p.id)).forEach(handle_playerjoin);
+			Object.entries(content.loot).forEach(([loot_uuid, lootObj]) => {
+				if (!(loot_uuid in worldLoot))
+					summon_loot(loot_uuid, lootObj);
+			});
+		}
+
+		const handle_leaveview = function(content){
+			content.players.forEach(handle_playerleave);
+			content.loot.forEach(forget_loot);
+		}
+
+		const forget_loot = function(loot_uuid){
+			if (!(loot_uuid in worldLoot))
+				return;
+			loot_container.removeChild(worldLoot[loot_uuid].graphics);
+			delete worldLoot[loot_uuid];
+		}
+
+		const spawn_explosion = function(posx, posy){
+			const emitJSON = JSON.parse(JSON.stringify(emitters["explosion"]));
+			emitJSON.pos = {
+				x: posx,
+				y: posy
+			};
+			emitJSON.behaviors.push({
+				type: 'textureSingle',
+				config: {
+					texture: PIXI.Texture.WHITE
+				}
+			});
+			let emitter = new PIXI.particles.Emitter(
+				world,
+				emitJSON
+			);
+			emitter.emit = true;
+			emissions.push(emitter);
 		}
 
 		const handle_playerdeath = function(content){
 			const is_self = content.from == public_id;
 			if (is_self){
-				if (content.loot){
+				if (content.loot && !(content.loot.uuid in worldLoot)){
 					summon_loot(content.loot.uuid, content.loot.object);
 				}
-				socket.send(
-					JSON.stringify({
-						"t":"Spawn"
-					})
-				);
-			} else {
-				const emitJSON = JSON.parse(JSON.stringify(emitters["explosion"]));
-				emitJSON.pos = {
-					x: gameState[content.from].p.trajectory.pos.x,
-					y: gameState[content.from].p.trajectory.pos.y
-				};
-				emitJSON.behaviors.push({
-					type: 'textureSingle',
-					config: {
-						texture: PIXI.Texture.WHITE
-					}
+				send({
+					"t":"Spawn"
 				});
-				let emitter = new PIXI.particles.Emitter(
-					world,
-					emitJSON
-				);
-				emitter.emit = true;
-				emissions.push(emitter);
-				if (content.loot){
+			} else {
+				if (content.loot && !(content.loot.uuid in worldLoot)){
 					summon_loot(content.loot.uuid, content.loot.object);
 				}
+				if (!(content.from in gameState))
+					return; //seen dying here before the server said so
+				spawn_explosion(gameState[content.from].p.trajectory.pos.x, gameState[content.from].p.trajectory.pos.y);
+				departed.add(content.from);
 				handle_playerleave(content.from);
 			}
 		}
@@ -752,18 +945,97 @@ async function runAll(){
 		}
 
 		const handle_correction = function(content){
-			gameState[content['id']].p.trajectory = new Trajectory(content['tr']);
+			correcting.delete(content['id']);
+			//replays the changes made since on top, the ship glides over instead of jumping
+			gameState[content['id']].p.trajectory.correct(content['tr']);
 			change_propulsion_emitter(content['id'], gameState[content['id']].p.trajectory.propelling);
 		}
 
+		//both trajectories were already corrected by the server, this only shows the impact
+		const handle_shipcontact = function(content){
+			const ships = [content.first, content.second].filter(pid => pid in gameState);
+			ships.forEach(pid => {
+				const shallow_copy = pid == public_id ? world.pivot:gameState[pid].graphics;
+				spawn_hit_emitter(shallow_copy.x, shallow_copy.y, gameState[pid].child.rotation);
+				if (pid == public_id)
+					update_healthbar(gameState[pid].p.trajectory.health);
+			});
+		}
+
+		//the flight is stepped here like a ship's trajectory, so it follows the server's arc
+		const spawn_projectile = function(id, flight, end, color, radius){
+			const graphics = new PIXI.Graphics();
+			graphics.beginFill(color);
+			graphics.drawCircle(0, 0, radius);
+			graphics.endFill();
+			projectiles_container.addChild(graphics);
+			projectiles[id] = {
+				trajectory: new Trajectory(flight),
+				end: end,
+				graphics: graphics,
+			};
+		}
+
+		const remove_projectile = function(id){
+			if (!(id in projectiles))
+				return;
+			projectiles_container.removeChild(projectiles[id].graphics);
+			delete projectiles[id];
+		}
+
+		const apply_projectile_hit = function(hit){
+			if (!(hit.id in gameState))
+				return;
+			if (hit.loot) {
+				handle_playerdeath({
+					from: hit.id,
+					loot: hit.loot,
+				});
+				return;
+			}
+			gameState[hit.id].p.trajectory.record_change(new UpdateTypeWrapper(UpdateType["Bullet"], hit.damage));
+			if (hit.id == public_id)
+				update_healthbar(gameState[hit.id].p.trajectory.health);
+		}
+
+		const handle_grenadethrown = function(content){
+			spawn_projectile(content.id, content.flight, content.fuse_end, 0x88ff44, grenade_radius);
+		}
+
+		const handle_grenadeexploded = function(content){
+			remove_projectile(content.id);
+			spawn_explosion(content.x, content.y);
+			content.hits.forEach(apply_projectile_hit);
+		}
+
+		const handle_bulletfired = function(content){
+			spawn_projectile(content.id, content.flight, content.expires, 0x44ccff, bolt_radius);
+		}
+
+		const handle_bulletimpact = function(content){
+			const bolt = projectiles[content.id];
+			remove_projectile(content.id);
+			if (content.hit) {
+				const rotation = bolt ? Math.atan2(bolt.trajectory.vel.y, bolt.trajectory.vel.x) - PI/2:0;
+				spawn_hit_emitter(content.x, content.y, rotation);
+				apply_projectile_hit(content.hit);
+			}
+		}
+
+		//the server couldn't confirm a hit we reported, the victim keeps its health
+		const handle_shotrejected = function(content){
+			console.warn(`shot rejected: ${content.reason}`);
+		}
+
+		const handle_warning = function(content){
+			console.warn(`warning from the server: ${content}`);
+		}
+
 		const handle_pong = function(content){
-			const original_rtt = current_rtt;
 			const now = local_time();
 			current_rtt = now - lastPing;
 			clocks_delta = now - content - Math.round(current_rtt/2);
 			latency_text.text = `latency: ${current_rtt}ms`;
-			if (original_rtt == null)
-				socket.send(JSON.stringify({"t":"StateQuery"}));
 		}
 
 		const handle_lootcollection = function(content){
@@ -793,14 +1065,29 @@ async function runAll(){
 					gameState[content.collector].p.speed += 1.0;
 				},
 				"Health": () => {
-					gameState[content.collector].p.trajectory.apply_change(new UpdateTypeWrapper(UpdateType["Health"], loot_value));
+					gameState[content.collector].p.trajectory.record_change(new UpdateTypeWrapper(UpdateType["Health"], loot_value));
 					update_healthbar(gameState[content.collector].p.trajectory.health);
+				},
+				"Fuel": () => {
+					gameState[content.collector].p.trajectory.record_change(new UpdateTypeWrapper(UpdateType["Refuel"], loot_value));
+					update_fuelbar(gameState[content.collector].p.trajectory.fuel);
 				}
 			}[loot_type])();
 		}
 
+		//sends a message in the protocol agreed on when connecting
+		const send = function(message) {
+			socket.send(binary ? encode_message(message) : JSON.stringify(message));
+		};
+
 		const recvFn = function(event) {
-			let data = JSON.parse(event.data);
+			let data;
+			try {
+				data = typeof event.data === "string" ? JSON.parse(event.data) : decode_message(new Uint8Array(event.data));
+			} catch (error) {
+				console.error(`Can't decode message from the server: ${error}`);
+				return;
+			}
 			console.log(data);
 			let datatype = data["t"];
 			let content = data["c"];
@@ -809,12 +1096,21 @@ async function runAll(){
 				"PlayerJoin": handle_playerjoin,
 				"PlayerLeave": handle_playerleave,
 				"GameState": handle_gamestate,
+				"EnterView": handle_enterview,
+				"LeaveView": handle_leaveview,
 				"TrajectoryUpdate": handle_update,
 				"PlayerDeath": handle_playerdeath,
 				"LootCollected": handle_lootcollection,
 				"Shoot": handle_shoot,
 				"Correct": handle_correction,
-				"LootReject": handle_rejection
+				"LootReject": handle_rejection,
+				"ShipContact": handle_shipcontact,
+				"ShotRejected": handle_shotrejected,
+				"Warning": handle_warning,
+				"GrenadeThrown": handle_grenadethrown,
+				"GrenadeExploded": handle_grenadeexploded,
+				"BulletFired": handle_bulletfired,
+				"BulletImpact": handle_bulletimpact
 			};
 			if (!(datatype in fmap)){
 				console.error(`received unknown server message: ${JSON.stringify(content)}`);
@@ -827,17 +1123,15 @@ async function runAll(){
 			const chAt = gameState[public_id].p.trajectory.hash_str();
 			const time = Number(gameState[public_id].p.trajectory.time);
 			const wrapper_obj = new UpdateTypeWrapper(UpdateType[utype]);
-			gameState[public_id].p.trajectory.apply_change(wrapper_obj);
-			socket.send(
-				JSON.stringify({
-					"t":"TrajectoryUpdate",
-					"c":{
-						"change": {"utype": utype},
-						"at": chAt,
-						"time": time,
-					 }
-				})
-			);		
+			gameState[public_id].p.trajectory.record_change(wrapper_obj);
+			send({
+				"t":"TrajectoryUpdate",
+				"c":{
+					"change": {"utype": utype},
+					"at": chAt,
+					"time": time,
+				 }
+			});		
 		}
 
 		const keyAction = function (repeated, name, up){
@@ -862,17 +1156,62 @@ async function runAll(){
 			} else if (name == keyup) {
 				let change = up ? "PropOff":"PropOn";
 				perform_update(change);
-				change_propulsion_emitter(public_id, !up);
-			} else if (name == keyshoot) {
+				//an empty tank keeps propulsion off
+				change_propulsion_emitter(public_id, gameState[public_id].p.trajectory.propelling);
+			} else if (keyslots.includes(name)) {
 				if (up) return;
-
+				const slot = keyslots.indexOf(name);
+				const inventory = gameState[public_id].p.inventory;
+				if (inventory.selection == slot)
+					return;
+				inventory.selection = slot;
+				grenade_held = false; //a grenade being held is put away
+				ammo_text.text = inventory.weapons[slot].ammo;
+				send({"t":"ChangeSlot","c":{"slot": slot}});
+			} else if (name == keyshoot) {
 				const inventory = gameState[public_id].p.inventory;
 				const selectedWeapon = inventory.weapons[inventory.selection];
 				if (selectedWeapon.ammo <= 0)
 					return;
 
+				//grenades are thrown on release, harder the longer the trigger was held
+				if (selectedWeapon.weptype == "Grenade") {
+					const trajectory = gameState[public_id].p.trajectory;
+					if (!up) {
+						grenade_held = true;
+						send({"t":"TriggerPress","c":{"time": Number(trajectory.time)}});
+						return;
+					}
+					if (!grenade_held)
+						return;
+					grenade_held = false;
+					ammo_text.text = --selectedWeapon.ammo;
+					send({
+						"t":"Shoot",
+						"c": {
+							"at": trajectory.hash_str(),
+							"stime": Number(trajectory.time),
+						},
+					});
+					return;
+				}
+				if (up) return;
+
 				ammo_text.text = --selectedWeapon.ammo;
 
+				//the bolt is drawn once the server fires it, it decides what it hits
+				if (selectedWeapon.weptype == "Blaster") {
+					const trajectory = gameState[public_id].p.trajectory;
+					send({
+						"t":"Shoot",
+						"c": {
+							"at": trajectory.hash_str(),
+							"stime": Number(trajectory.time),
+						},
+					});
+					return;
+				}
+
 				let closestHit = {
 					distance: 999999999,
 					hit: false,
@@ -909,12 +1248,10 @@ async function runAll(){
 					spawn_hit_emitter(shallow_copy.x, shallow_copy.y, line_rotation);
 				}
 
-				socket.send(
-					JSON.stringify({
-						"t":"Shoot",
-						"c": shootMsg,
-					})
-				);
+				send({
+					"t":"Shoot",
+					"c": shootMsg,
+				});
 			} else if (name == keyshop) {
 				document.getElementById("shop-modal").style.display = keymap[name] ? "flex":"none";
 			} else if ((name == keyzoomout || name == keyzoomin) && !up) {
@@ -954,7 +1291,10 @@ async function runAll(){
 					return;
 				}
 				if (pid == public_id){
-					player.p.trajectory.advance(BigInt(server_time()), false);
+					const result = player.p.trajectory.advance(BigInt(server_time()), false);
+					if (result && JSON.parse(result).indexOf("PropOff") !== -1)
+						change_propulsion_emitter(pid, false); //ran out of fuel
+					update_fuelbar(player.p.trajectory.fuel);
 					return;
 				}
 
@@ -962,7 +1302,7 @@ async function runAll(){
 
 				let result = player.p.trajectory.advance(ptime, false);
 				if (!result) {
-					socket.send(JSON.stringify({"t": "Correct", "c": pid}));
+					request_correction(pid);
 					return;
 				}
 
@@ -981,7 +1321,7 @@ async function runAll(){
 					return;
 				const shallow_copy = player.p.id == public_id ? world.pivot:player.graphics
 
-				//lerp the positions
+				//interpolate the positions between steps
 				let now;
 				if (player.p.id == public_id){
 					now = server_time();
@@ -989,15 +1329,38 @@ async function runAll(){
 					now = server_time() - (50 + current_rtt); //make other players appear in the past
 				}
 
-				const lerped = player.p.trajectory.lerp(BigInt(now));
+				const drawn = player.p.trajectory.interpolate(BigInt(now));
 				
-				shallow_copy.x = lerped.x;
-				shallow_copy.y = lerped.y;
-				player.child.rotation = lerped.r;
+				shallow_copy.x = drawn.x;
+				shallow_copy.y = drawn.y;
+				player.child.rotation = drawn.r;
+			});
+
+			Object.values(projectiles).forEach(projectile => {
+				const now = Math.min(server_time(), projectile.end);
+				projectile.trajectory.advance(BigInt(now));
+				const drawn = projectile.trajectory.interpolate(BigInt(now));
+				projectile.graphics.position.set(drawn.x, drawn.y);
+			});
+
+			//bodies may be orbiting, their positions only depend on the time
+			const positions = body_positions(BigInt(server_time()));
+			bodies_container.children.forEach((body_obj, i) => {
+				body_obj.position.set(positions[2*i], positions[2*i+1]);
 			});
 
 			coords_text.text = `x: ${Math.round(world.pivot.x)}, y: ${-Math.round(world.pivot.y)}`;
 
+			draw_prediction();
+
+			const current_dome_radius = dome_radius(BigInt(server_time()));
+			if (current_dome_radius != drawn_dome_radius) {
+				dome_line.clear();
+				dome_line.lineStyle(6, 0xff4444, 0.6);
+				dome_line.drawCircle(0, 0, current_dome_radius);
+				drawn_dome_radius = current_dome_radius;
+			}
+
 			const tile_x = Math.floor(world.pivot.x / (bg_w*background_scale));
 			const tile_y = Math.floor(world.pivot.y / (bg_h*background_scale));
 			const tiles_key = tile_x + "_" + tile_y;
@@ -1029,11 +1392,11 @@ async function runAll(){
 			Object.entries(worldLoot).forEach(([loot_id, lootObj]) => {
 				if (lootObj.claimed)
 					return;
-				const trig = Math.pow(lootObj.l.x - world.pivot.x, 2) + Math.pow(lootObj.l.y - world.pivot.y, 2) < 40*40;
+				const trig = Math.pow(lootObj.l.x - world.pivot.x, 2) + Math.pow(lootObj.l.y - world.pivot.y, 2) < claim_radius*claim_radius;
 				if (!trig)
 					return;
 				lootObj.claimed = true;
-				socket.send(JSON.stringify({"t":"ClaimLoot","c":{"loot_id": loot_id}}));
+				send({"t":"ClaimLoot","c":{"loot_id": loot_id}});
 			});
 		});
 
@@ -1048,6 +1411,29 @@ async function runAll(){
 		window.addEventListener('keydown', (event) => keyAction(event.repeat, event.key, false));
 		window.addEventListener('keyup', (event) => keyAction(event.repeat, event.key, true));
 
+		//draws the orbit line of the own ship and warns if it ends in a body
+		function draw_prediction() {
+			prediction_line.clear();
+			crash_text.text = "";
+			const own = gameState[public_id];
+			if (own === undefined || own.p.trajectory.health == 0)
+				return;
+
+			const prediction = JSON.parse(own.p.trajectory.predict(prediction_steps));
+			prediction_line.lineStyle(2, 0xffffff, 0.3);
+			prediction_line.moveTo(world.pivot.x, world.pivot.y);
+			for (let i = 0; i < prediction.path.length; i += 2)
+				prediction_line.lineTo(prediction.path[i], prediction.path[i+1]);
+
+			if (prediction.crash !== null) {
+				const crash = prediction.crash;
+				prediction_line.lineStyle(2, 0xff4444, 0.8);
+				prediction_line.drawCircle(crash.x, crash.y, player_radius);
+				const seconds = Math.max(0, (crash.time - server_time()) / 1000);
+				crash_text.text = `crash in ${seconds.toFixed(1)} s`;
+			}
+		}
+
 		//guesses time of server based on latency calculations
 		function server_time() {
 			return local_time() - clocks_delta;
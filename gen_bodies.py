@@ -7,11 +7,10 @@ max_y = 5000
 min_r = 50
 max_r = 150
 
+#prints a map file for the server, e.g. python3 gen_bodies.py > maps/random.toml
+print("dome_radius = 6000.0")
 for x in range(25):
-	print("	Body {")
-	print("		pos: Vector{")
-	print(f"			x: {randint(min_x, max_x)}.0,")
-	print(f"			y: {randint(min_y, max_y)}.0,")
-	print("		},")
-	print(f"		radius: {randint(min_r, max_r)}.0")
-	print("	},")
+	print()
+	print("[[bodies]]")
+	print(f"pos = {{ x = {randint(min_x, max_x)}.0, y = {randint(min_y, max_y)}.0 }}")
+	print(f"radius = {randint(min_r, max_r)}.0")
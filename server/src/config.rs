@@ -0,0 +1,437 @@
+use std::env;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use toml::{Table, Value};
+
+use utils::bullet::Blaster;
+use utils::conduct::Policy;
+use utils::grenade::Blast;
+use utils::limits::Limits;
+use utils::shared_gameobjects::get_cost;
+use utils::trajectory::{Integrator, UpdateType};
+
+//How the server is set up. Settings are read from a TOML file, then environment variables and
+//then command line flags, each overriding the ones before. Everything in the file can also be
+//set with --set, the settings used most have flags and variables of their own.
+
+pub const HELP: &str = "Usage: game [options] [port] [map file] [integrator]
+
+Options:
+  --config <file>        TOML file with the settings, server.toml if there is one [GAME_CONFIG]
+  --address <ip>         address to listen on, 0.0.0.0 [GAME_ADDRESS]
+  --port <port>          port to listen on, 8000 [GAME_PORT]
+  --map <file>           map to play on, maps/default.toml [GAME_MAP]
+  --integrator <name>    euler, verlet or leapfrog instead of the map's choice [GAME_INTEGRATOR]
+  --assets <dir>         where the client is served from, client [GAME_ASSETS]
+  --tick-rate <n>        ticks per second from 1 to 1000, 10 [GAME_TICK_RATE]
+  --admin-token <token>  enables the /admin/suspects report [GAME_ADMIN_TOKEN]
+  --set <key>=<value>    any setting of the file, like --set gameplay.loot_radius=80
+  -h, --help             print this
+
+Settings in the file besides the ones above:
+  [limits]               rates = { Shoot = { per_second = 10, burst = 20 } }, total, tolerance, max_frame
+                         AckSnapshot is limited to the tick rate, on top of total
+  [conduct]              half_life in millis, thresholds = [[10, \"Warn\"], [25, \"ShadowMute\"], ...]
+  [gameplay]             loot_radius, starting_cash, boost_price, refuel_price, pistol_damage
+  [gameplay.blaster]     muzzle_speed, lifetime in millis, damage
+  [gameplay.grenade]     fuse in millis, radius, damage at the centre
+  [gameplay.drops]       cash_share, ammo, fuel, health: what a dead player leaves behind
+  trusted_proxies        [\"127.0.0.1\"], proxies whose X-Forwarded-For names the player's address
+";
+
+const DEFAULT_FILE: &str = "server.toml"; //read if it is there and no other file is given
+
+//the settings with a flag and a variable of their own: flag, variable, key and whether it is text
+const SETTINGS: [(&str, &str, &str, bool); 7] = [
+	("--address", "GAME_ADDRESS", "address", true),
+	("--port", "GAME_PORT", "port", false),
+	("--map", "GAME_MAP", "map", true),
+	("--integrator", "GAME_INTEGRATOR", "integrator", true),
+	("--assets", "GAME_ASSETS", "assets", true),
+	("--tick-rate", "GAME_TICK_RATE", "tick_rate", false),
+	("--admin-token", "GAME_ADMIN_TOKEN", "admin_token", true),
+];
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+	pub address: IpAddr,
+	pub port: u16,
+	pub map: PathBuf,
+	pub integrator: Option<Integrator>, //overrides the map's choice, clients get it with the map
+	pub assets: PathBuf,
+	pub tick_rate: u64,
+	pub admin_token: Option<String>, //for the report of suspected cheaters
+	pub trusted_proxies: Vec<IpAddr>,
+	pub limits: Limits, //what clients may send, the same for all of them
+	pub conduct: Policy,
+	pub gameplay: Gameplay,
+}
+
+impl Default for Config {
+	fn default() -> Config {
+		Config {
+			address: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+			port: 8000,
+			map: PathBuf::from("maps/default.toml"),
+			integrator: None,
+			assets: PathBuf::from("client"),
+			tick_rate: 10,
+			admin_token: None,
+			trusted_proxies: Vec::new(),
+			limits: Limits::default(),
+			conduct: Policy::default(),
+			gameplay: Gameplay::default(),
+		}
+	}
+}
+
+//clients are sent these from /gameplay, so they charge and claim the way the server checks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Gameplay {
+	pub loot_radius: f32, //players must be within this distance to claim
+	pub starting_cash: u32,
+	pub boost_price: u32,
+	pub refuel_price: u32,
+	pub pistol_damage: u8, //clients take it off the ships they hit before the server says so
+	pub blaster: Blaster,
+	pub grenade: Blast,
+	pub drops: Drops,
+}
+
+impl Default for Gameplay {
+	fn default() -> Gameplay {
+		Gameplay {
+			loot_radius: 60.0,
+			starting_cash: 20,
+			boost_price: get_cost(UpdateType::AddBoost),
+			refuel_price: get_cost(UpdateType::Refuel),
+			pistol_damage: 25,
+			blaster: Blaster::default(),
+			grenade: Blast::default(),
+			drops: Drops::default(),
+		}
+	}
+}
+
+impl Gameplay {
+	pub fn price(&self, utype: &UpdateType) -> u32 {
+		match utype {
+			UpdateType::AddBoost => self.boost_price,
+			UpdateType::Refuel => self.refuel_price,
+			_ => 0,
+		}
+	}
+
+	fn validate(&self) -> Result<(), String> {
+		if self.loot_radius.is_nan() || self.loot_radius <= 0.0 {
+			return Err("loot_radius has to be positive".to_string());
+		}
+		if self.pistol_damage == 0 || self.blaster.damage == 0 {
+			return Err("weapons have to do some damage".to_string());
+		}
+		if !(1.0..).contains(&self.blaster.muzzle_speed) || self.blaster.lifetime == 0 {
+			return Err("blaster bolts have to fly for a while".to_string());
+		}
+		if self.grenade.fuse == 0 || !(1.0..).contains(&self.grenade.radius) {
+			return Err("grenade.fuse and grenade.radius have to be positive".to_string());
+		}
+		if !(1.0..=255.0).contains(&self.grenade.damage) {
+			return Err("grenade.damage has to be from 1 to 255".to_string());
+		}
+		if !(0.0..=1.0).contains(&self.drops.cash_share) {
+			return Err("drops.cash_share has to be from 0 to 1".to_string());
+		}
+		if self.drops.fuel > 100 {
+			return Err("drops.fuel is a percentage of a full tank, at most 100".to_string());
+		}
+		Ok(())
+	}
+}
+
+//what a dead player leaves behind, one of them with about the same chance each
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Drops {
+	pub cash_share: f32, //of the dead player's cash
+	pub ammo: u32,
+	pub fuel: u8, //percentage of a full tank
+	pub health: u8,
+}
+
+impl Default for Drops {
+	fn default() -> Drops {
+		Drops {
+			cash_share: 0.5,
+			ammo: 15,
+			fuel: 50,
+			health: 30,
+		}
+	}
+}
+
+impl Config {
+	//The settings from the file, the environment and then args, without the program's name
+	pub fn load(args: &[String]) -> Result<Config, String> {
+		Config::load_with(args, |variable| env::var(variable).ok())
+	}
+
+	//the same with the environment variables looked up by var
+	fn load_with(args: &[String], var: impl Fn(&str) -> Option<String>) -> Result<Config, String> {
+		let mut overrides = Table::new();
+		let mut file = var("GAME_CONFIG").map(PathBuf::from);
+		for (_, variable, key, text) in SETTINGS {
+			if let Some(value) = var(variable) {
+				set(&mut overrides, key, parse_value(&value, text))
+					.map_err(|e| format!("{}: {}", variable, e))?;
+			}
+		}
+
+		let mut positional = ["port", "map", "integrator"].into_iter();
+		let mut args = args.iter();
+		while let Some(arg) = args.next() {
+			let (flag, inline) = match arg.split_once('=') {
+				Some((flag, value)) if arg.starts_with("--") => (flag, Some(value.to_string())),
+				_ => (arg.as_str(), None),
+			};
+			if !flag.starts_with("--") {
+				let key = positional
+					.next()
+					.ok_or_else(|| format!("Unexpected argument {}, see --help", arg))?;
+				set(&mut overrides, key, parse_value(arg, key != "port"))?;
+				continue;
+			}
+			let value = inline
+				.or_else(|| args.next().cloned())
+				.ok_or_else(|| format!("{} needs a value, see --help", flag))?;
+			match flag {
+				"--config" => file = Some(PathBuf::from(value)),
+				"--set" => {
+					let (key, value) = value
+						.split_once('=')
+						.ok_or_else(|| format!("--set needs key=value, not {}", value))?;
+					set(&mut overrides, key.trim(), parse_value(value.trim(), false))
+						.map_err(|e| format!("--set {}: {}", key, e))?;
+				}
+				_ => {
+					let (_, _, key, text) = SETTINGS
+						.iter()
+						.find(|(name, ..)| *name == flag)
+						.ok_or_else(|| format!("Unknown option {}, see --help", flag))?;
+					set(&mut overrides, key, parse_value(&value, *text))
+						.map_err(|e| format!("{}: {}", flag, e))?;
+				}
+			}
+		}
+
+		let mut settings = match file {
+			Some(path) => read_file(&path)?,
+			None if Path::new(DEFAULT_FILE).exists() => read_file(Path::new(DEFAULT_FILE))?,
+			None => Table::new(),
+		};
+		merge(&mut settings, overrides);
+		let config: Config = Value::Table(settings)
+			.try_into()
+			.map_err(|e| format!("Invalid configuration: {}", e.to_string().trim_end()))?;
+		config.validate()?;
+		Ok(config)
+	}
+
+	//The address a player connects from, what bans go by. Connections from a trusted proxy are
+	//from the last address in X-Forwarded-For that no trusted proxy has, None when there isn't
+	//one so that the proxy itself is never banned.
+	pub fn client_address(&self, peer: Option<IpAddr>, forwarded: Option<&str>) -> Option<IpAddr> {
+		let peer = peer?;
+		if !self.trusted_proxies.contains(&peer) {
+			return Some(peer);
+		}
+		for hop in forwarded?.rsplit(',') {
+			match hop.trim().parse() {
+				Ok(address) if self.trusted_proxies.contains(&address) => continue,
+				Ok(address) => return Some(address),
+				Err(_) => return None,
+			}
+		}
+		None
+	}
+
+	fn validate(&self) -> Result<(), String> {
+		if !(1..=1000).contains(&self.tick_rate) {
+			return Err(
+				"tick_rate has to be a number of ticks per second from 1 to 1000".to_string(),
+			);
+		}
+		if !self.assets.join("site.html").is_file() {
+			return Err(format!(
+				"assets has to be the client's directory, there is no site.html in {}",
+				self.assets.display()
+			));
+		}
+		if self.limits.rates.contains_key("AckSnapshot") {
+			return Err(
+				"Invalid limits: AckSnapshot follows tick_rate and can't be set".to_string(),
+			);
+		}
+		self.limits
+			.validate()
+			.map_err(|e| format!("Invalid limits: {}", e))?;
+		self.conduct
+			.validate()
+			.map_err(|e| format!("Invalid conduct: {}", e))?;
+		self.gameplay
+			.validate()
+			.map_err(|e| format!("Invalid gameplay: {}", e))
+	}
+}
+
+fn read_file(path: &Path) -> Result<Table, String> {
+	let data = fs::read_to_string(path)
+		.map_err(|e| format!("Can't read config {}: {}", path.display(), e))?;
+	data.parse::<Table>().map_err(|e| {
+		format!(
+			"Invalid config {}: {}",
+			path.display(),
+			e.to_string().trim_end()
+		)
+	})
+}
+
+//a value as written in TOML, text can also be left unquoted
+fn parse_value(raw: &str, text: bool) -> Value {
+	if text {
+		return Value::String(raw.to_string());
+	}
+	format!("value = {}", raw)
+		.parse::<Table>()
+		.ok()
+		.and_then(|mut table| table.remove("value"))
+		.unwrap_or_else(|| Value::String(raw.to_string()))
+}
+
+//sets a dotted key like limits.max_frame
+fn set(table: &mut Table, key: &str, value: Value) -> Result<(), String> {
+	let mut path: Vec<&str> = key.split('.').collect();
+	let last = path.pop().unwrap_or_default();
+	let mut table = table;
+	for part in path {
+		table = table
+			.entry(part)
+			.or_insert_with(|| Value::Table(Table::new()))
+			.as_table_mut()
+			.ok_or_else(|| format!("{} isn't a table of settings", part))?;
+	}
+	table.insert(last.to_string(), value);
+	Ok(())
+}
+
+//the overrides replace what they set and leave the rest of each table
+fn merge(settings: &mut Table, overrides: Table) {
+	for (key, value) in overrides {
+		match (settings.get_mut(&key), value) {
+			(Some(Value::Table(inner)), Value::Table(value)) => merge(inner, value),
+			(_, value) => {
+				settings.insert(key, value);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn players_behind_a_trusted_proxy_are_told_apart() {
+		let ip = |text: &str| text.parse::<IpAddr>().unwrap();
+		let config = Config {
+			trusted_proxies: vec![ip("10.0.0.1"), ip("10.0.0.2")],
+			..Config::default()
+		};
+		let player = Some(ip("203.0.113.7"));
+		assert_eq!(config.client_address(player, None), player);
+		//what others claim in the header is ignored
+		assert_eq!(config.client_address(player, Some("198.51.100.1")), player);
+
+		let proxy = Some(ip("10.0.0.1"));
+		let forwarded = Some("198.51.100.1, 203.0.113.7, 10.0.0.2");
+		assert_eq!(config.client_address(proxy, forwarded), player);
+		//without a header the proxy isn't taken for the player
+		assert_eq!(config.client_address(proxy, None), None);
+		assert_eq!(config.client_address(proxy, Some("10.0.0.2")), None);
+		assert_eq!(config.client_address(proxy, Some("somewhere")), None);
+	}
+
+	fn args(line: &str) -> Vec<String> {
+		line.split_whitespace().map(String::from).collect()
+	}
+
+	#[test]
+	fn flags_override_variables_that_override_the_file() {
+		let file = env::temp_dir().join(format!("game-config-{}.toml", std::process::id()));
+		fs::write(
+			&file,
+			"port = 7000\ntick_rate = 20\nassets = \"../client\"\n[gameplay]\nboost_price = 3\nrefuel_price = 4",
+		)
+		.unwrap();
+		let path = file.display().to_string();
+		let env = |variable: &str| match variable {
+			"GAME_CONFIG" => Some(path.clone()),
+			"GAME_TICK_RATE" => Some("30".to_string()),
+			"GAME_ADMIN_TOKEN" => Some("secret".to_string()),
+			//only the game's own variables are read
+			"ADMIN_TOKEN" => Some("someone else's".to_string()),
+			"GAME_MAP" => Some("maps/other.toml".to_string()),
+			_ => None,
+		};
+		let config = Config::load_with(&args("--tick-rate=40 --set gameplay.boost_price=9"), env);
+		fs::remove_file(&file).unwrap();
+		let config = config.unwrap();
+		assert_eq!(config.port, 7000);
+		assert_eq!(config.tick_rate, 40);
+		assert_eq!(config.map, PathBuf::from("maps/other.toml"));
+		assert_eq!(config.admin_token.as_deref(), Some("secret"));
+		//--set only replaces what it names
+		assert_eq!(config.gameplay.boost_price, 9);
+		assert_eq!(config.gameplay.refuel_price, 4);
+		assert_eq!(
+			config.gameplay.starting_cash,
+			Gameplay::default().starting_cash
+		);
+	}
+
+	#[test]
+	fn positional_arguments_are_port_map_and_integrator() {
+		let config = Config::load_with(
+			&args("--assets ../client 3999 maps/small.toml leapfrog"),
+			|_| None,
+		)
+		.unwrap();
+		assert_eq!(config.port, 3999);
+		assert_eq!(config.map, PathBuf::from("maps/small.toml"));
+		assert_eq!(config.integrator, Some(Integrator::Leapfrog));
+		let extra = Config::load_with(&args("--assets ../client 1 a euler more"), |_| None);
+		assert!(extra.unwrap_err().starts_with("Unexpected argument more"));
+	}
+
+	#[test]
+	fn bad_settings_are_refused() {
+		let load = |line: &str| {
+			Config::load_with(&args(&format!("--assets ../client {}", line)), |_| None)
+		};
+		assert!(load("").is_ok());
+		assert!(load("--tick-rate 0").is_err());
+		assert!(load("--port").is_err());
+		assert!(load("--volume 11").is_err());
+		assert!(load("--set gameplay.loot_radius").is_err());
+		assert!(load("--set gameplay.loot_radius=-1").is_err());
+		assert!(load("--set gameplay.loot_range=80").is_err());
+		assert!(load("--set limits.rates.Shout={per_second=1,burst=1}").is_err());
+		assert!(load("--set limits.rates.AckSnapshot={per_second=1,burst=1}").is_err());
+		assert!(load("--config /nowhere/game.toml").is_err());
+		assert!(Config::load_with(&args("--assets ."), |_| None).is_err());
+	}
+}
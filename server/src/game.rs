@@ -1,87 +1,66 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::error::Error;
+use std::time::Duration;
 
 use futures::future::join_all;
 use rand::rngs::StdRng;
 use rand::Rng;
 use rand::SeedableRng;
-use serde_json::{from_str, json, to_string, Value};
+use serde_json::to_string;
 use uuid::Uuid;
 use warp::ws::Message;
 
+use crate::config;
 use crate::handler::spawn_from_prev;
 use crate::Client;
 use crate::Clients;
+use crate::Interests;
+use crate::Projectiles;
+use crate::Suspects;
 use crate::WorldLoot;
 
+use utils::bullet::{Bullet, Impact};
+use utils::conduct::{Action, Offence};
+use utils::grenade::Grenade;
+use utils::history::{advance_together, TrajectoryHistory};
+use utils::interest::{Spot, View};
+use utils::messages::*;
+use utils::protocol::{to_binary, Codec};
+use utils::scalar::Real;
 use utils::server_gameobjects::*;
-use utils::shared_gameobjects::*;
 use utils::trajectory::*;
 
-const LOOT_RADIUS: f32 = 60.0; //players must be within this distance to claim
+const GRENADE_INTERVAL: u64 = 100; //millis between stepping the grenades in flight
+const BULLET_INTERVAL: u64 = 50; //millis between stepping the bullets in flight
+const DEATH_GRACE: u64 = 1000; //millis after finding a player dead that its client may not know
 
 impl Client {
-	pub async fn transmit(
-		&self,
-		msg: &ServerMessage,
-		public_id: Option<String>,
-	) -> Result<(), Box<dyn Error>> {
+	pub async fn transmit(&self, msg: &ServerMessage) -> Result<(), Box<dyn Error>> {
 		if let Some(ch) = self.sender.as_ref() {
-			let public_id = match public_id {
-				Some(id) => id,
-				None => self.state.read().await.id.clone(),
-			};
-			let serialized_msg = match msg {
-				ServerMessage::PlayerJoin(_)
-				| ServerMessage::GameState {
-					pstates: _,
-					worldloot: _,
-				} => {
-					//ServerMessage::LootCollected{loot_id: _, collector: _} => {
-					match msg {
-						ServerMessage::GameState { pstates, worldloot } => {
-							let encoded_states: Vec<Value> = pstates
-								.iter()
-								.map(|state| state.encode(public_id == state.id))
-								.collect();
-							to_string(&json!({
-								"t": "GameState",
-								"c": {
-									"players": encoded_states,
-									"loot": worldloot,
-								}
-							}))?
-						}
-						ServerMessage::PlayerJoin(pstate) => to_string(&json!({
-							"t": "PlayerJoin",
-							"c": pstate.encode(pstate.id == public_id),
-						}))?,
-						_ => String::new(),
-					}
-				}
-				_ => to_string(msg)?,
-			};
-			ch.send(Ok(Message::text(serialized_msg)))
+			ch.send(Ok(self.frame(msg)?))
 				.map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
 		}
 		Ok(())
 	}
+
+	//a message in the codec agreed on with the client
+	fn frame(&self, message: &ServerMessage) -> Result<Message, Box<dyn Error>> {
+		Ok(match self.codec {
+			Codec::Json => Message::text(to_string(message)?),
+			Codec::Binary => Message::binary(to_binary(message)?),
+		})
+	}
 }
 
 pub async fn handle_game_message(
 	public_id: String,
-	message: &str,
+	message: ClientMessage,
 	clients: &Clients,
 	world_loot: &WorldLoot,
+	projectiles: &Projectiles,
+	interests: &Interests,
+	suspects: &Suspects,
 ) -> Result<(), Box<dyn Error>> {
-	let message: ClientMessage = match from_str(message) {
-		Ok(v) => v,
-		Err(m) => {
-			eprintln!("Can't deserialize message: {}", m);
-			return Ok(());
-		}
-	};
-
 	let time_now = current_time();
 
 	let clr = clients.read().await;
@@ -101,6 +80,7 @@ pub async fn handle_game_message(
 	}
 	let is_allowed = match message {
 		ClientMessage::StateQuery => true,
+		ClientMessage::AckSnapshot { .. } => true, //the dead are sent snapshots too
 		ClientMessage::Ping => true,
 		ClientMessage::Spawn => state.trajectory.health == 0u8, //You have to be dead to call spawn
 		_ => state.trajectory.health > 0u8,                     //You have to be alive to call the rest
@@ -108,124 +88,144 @@ pub async fn handle_game_message(
 
 	if !is_allowed {
 		eprintln!("Rejected message {:?}", message);
+		//A dead ship stops at the time it died, which the server finds out about up to
+		//MAX_TIME_BEFORE later. What the client sent before it was told isn't held against it.
+		let told = state.trajectory.time + MAX_TIME_BEFORE + DEATH_GRACE;
+		if state.trajectory.health == 0 && time_now < told {
+			return Ok(());
+		}
+		let offence = Offence::DeadCommand {
+			message: message.name().to_string(),
+		};
+		return suspect(&public_id, offence, suspects, &clr).await;
+	}
+	//shadow muted players aren't told that what they do to others is dropped
+	let harmful = matches!(
+		message,
+		ClientMessage::TriggerPress { .. }
+			| ClientMessage::Shoot(_)
+			| ClientMessage::ClaimLoot { .. }
+	);
+	if harmful && suspects.read().await.muted(&public_id, time_now) {
 		return Ok(());
 	}
 
 	match message {
 		ClientMessage::Ping => {
 			if let Some(client) = clr.get(&public_id) {
-				client
-					.transmit(&ServerMessage::Pong(time_now), Some(public_id.to_string()))
-					.await?;
+				client.transmit(&ServerMessage::Pong(time_now)).await?;
 			} else {
 				eprintln!("Can't find client");
 			}
 		}
 		ClientMessage::AckPong => {}
 		ClientMessage::Spawn => {
-			spawn_from_prev(&mut *sender_state.write().await); //reset health and position
-			broadcast(
-				&ServerMessage::PlayerJoin(sender_state.read().await.clone()),
+			//reset health and position
+			spawn_from_prev(&mut *sender_state.write().await);
+			let spawned = sender_state.read().await.clone();
+			if let Some(client) = clr.get(&public_id) {
+				client
+					.transmit(&ServerMessage::PlayerJoin(spawned.view(true)))
+					.await?;
+			}
+			//the others are told when it comes into their view
+			let mut audience = interests.read().await.audience(&[&public_id], None);
+			audience.remove(&public_id);
+			broadcast_to(
+				&ServerMessage::PlayerJoin(spawned.view(false)),
+				&audience,
 				&clr,
 			)
-			.await; //broadcast playerjoin
+			.await;
 		}
 		ClientMessage::TrajectoryUpdate { change, time, at } => {
-			let cost = get_cost(change.clone().utype);
+			let cost = config().gameplay.price(&change.utype);
 			if state.cash < cost {
 				eprintln!(
 					"{} attempted to buy something without enough cash",
 					public_id
 				);
-				return Ok(());
+				let offence = Offence::UnaffordablePurchase {
+					cost,
+					cash: state.cash,
+				};
+				return suspect(&public_id, offence, suspects, &clr).await;
 			}
-			let successful: bool;
-			let updated_trajectory = {
+			let updated = {
 				let mut writeable = sender_state.write().await;
-				successful =
+				let updated =
 					writeable
 						.trajectory
 						.update(change.clone(), at.clone(), time, time_now);
-				if successful {
+				if updated.is_ok() {
 					writeable.cash -= cost;
+					writeable.record(Some(change.clone()));
 				} else {
 					writeable.trajectory.advance(time_now);
 				}
-				writeable.trajectory.clone()
+				updated.map_err(|reason| (reason, writeable.trajectory.clone()))
 			};
-			if successful {
-				//if accepted by the server, broadcast change
-				broadcast(
-					&ServerMessage::TrajectoryUpdate {
-						change: change,
-						time: time,
-						at: at,
-						from: public_id.clone(),
-					},
-					&clr,
-				)
-				.await;
-			} else {
+			if let Err((reason, updated_trajectory)) = updated {
 				//if rejected, correct the client
+				eprintln!("{}", reason);
 				println!("Correcting");
 				if let Some(client) = clr.get(&public_id) {
 					client
-						.transmit(
-							&ServerMessage::Correct {
-								id: public_id.clone(),
-								tr: updated_trajectory.to_b64(),
-							},
-							Some(public_id.clone()),
-						)
+						.transmit(&ServerMessage::Correct {
+							id: public_id.clone(),
+							tr: updated_trajectory,
+						})
 						.await?;
 				} else {
 					eprintln!("Can't find client");
 				}
+				let offence = Offence::RejectedUpdate { reason };
+				suspect(&public_id, offence, suspects, &clr).await?;
+			} else {
+				//if accepted by the server, tell everyone who can see the player
+				broadcast_to(
+					&ServerMessage::TrajectoryUpdate {
+						change,
+						time,
+						at,
+						from: public_id.clone(),
+					},
+					&interests.read().await.audience(&[&public_id], None),
+					&clr,
+				)
+				.await;
 			}
 		}
 		ClientMessage::StateQuery => {
-			//TODO rate limit this
-			eprintln!("Got statequery");
-
-			//gpt-4 did this
-			// Use futures::future::join_all to wait for all tasks to complete.
-			let players_futures: Vec<_> =
-				clr.iter().map(|(_, value)| value.state.write()).collect();
-
-			let players: Vec<_> = join_all(players_futures)
-				.await
-				.into_iter()
-				.filter_map(|mut lock| {
-					lock.trajectory.advance_to_min_time(time_now);
-					//lock.trajectory.advance_to_time(time_now);
-					let clone = lock.clone();
-					match clone.trajectory.health > 0u8 {
-						//to only send the living ones
-						true => Some(clone),
-						false => None,
-					}
-				})
-				.collect();
-
-			//Send the response to the client.
 			if let Some(client) = clr.get(&public_id) {
-				client
-					.transmit(
-						&ServerMessage::GameState {
-							pstates: players,
-							worldloot: world_loot.read().await.clone(),
-						},
-						Some(public_id),
-					)
-					.await?;
-			} else {
-				eprintln!("Can't find client")
+				client.snapshots.write().await.resync(); //the next snapshot lists everything
+			}
+		}
+		ClientMessage::AckSnapshot { sequence } => {
+			if let Some(client) = clr.get(&public_id) {
+				client.snapshots.write().await.ack(sequence);
 			}
 		}
 		ClientMessage::ChangeSlot { slot } => {
-			//TODO consider also changing trigger_pressed to false both on client and server when changeslot
 			if sender_state.read().await.clone().inventory.selection != slot {
-				sender_state.write().await.inventory.selection = slot;
+				let mut writeable = sender_state.write().await;
+				writeable.inventory.selection = slot;
+				writeable.trigger_pressed = false; //a grenade being held is put away
+			}
+		}
+		ClientMessage::TriggerPress { time } => {
+			let mut writer = sender_state.write().await;
+			let writeable = &mut *writer;
+			if let Some(Weapon {
+				weptype: WeaponType::Grenade { press_time },
+				..
+			}) = writeable
+				.inventory
+				.weapons
+				.get_mut(&writeable.inventory.selection)
+			{
+				*press_time = time.clamp(time_now - MAX_TIME_BEFORE, time_now + MAX_TIME_AHEAD);
+				writeable.trigger_pressed = true;
 			}
 		}
 		ClientMessage::Shoot(mut shoot_info) => {
@@ -238,9 +238,15 @@ pub async fn handle_game_message(
 				.clone();
 
 			//check if there is even ammo
-			if weapon_selected.ammo <= 0 {
+			if weapon_selected.ammo == 0 {
 				return Ok(());
 			}
+			if matches!(weapon_selected.weptype, WeaponType::Grenade { .. })
+				&& !state.trigger_pressed
+			{
+				let reason = "Grenade thrown without pressing the trigger".to_string();
+				return reject_shot(&public_id, reason, suspects, &clr).await;
+			}
 
 			{
 				let mut writeable = sender_state.write().await;
@@ -250,60 +256,121 @@ pub async fn handle_game_message(
 					.get_mut(&state.inventory.selection)
 					.unwrap()
 					.ammo -= 1;
-				state = writeable.clone();
+				writeable.trigger_pressed = false;
+			}
+
+			if let WeaponType::Grenade { press_time } = weapon_selected.weptype {
+				let from = match validate_shooter(&state.history, &shoot_info, time_now) {
+					Ok(from) => from,
+					Err(reason) => return reject_shot(&public_id, reason, suspects, &clr).await,
+				};
+				let grenade = Grenade::throw(
+					public_id.clone(),
+					&from,
+					shoot_info.stime.saturating_sub(press_time),
+					&config().gameplay.grenade,
+				);
+				let grenade_id = Uuid::new_v4().as_simple().to_string();
+				let audience = interests
+					.read()
+					.await
+					.audience(&[&public_id], Some(place_of(&from)));
+				let thrown = ServerMessage::GrenadeThrown {
+					id: grenade_id.clone(),
+					thrower: public_id,
+					flight: grenade.flight.clone(),
+					fuse_end: grenade.fuse_end,
+				};
+				projectiles
+					.write()
+					.await
+					.grenades
+					.insert(grenade_id, grenade);
+				broadcast_to(&thrown, &audience, &clr).await;
+				return Ok(());
+			}
+			if let WeaponType::Blaster = weapon_selected.weptype {
+				let from = match validate_shooter(&state.history, &shoot_info, time_now) {
+					Ok(from) => from,
+					Err(reason) => return reject_shot(&public_id, reason, suspects, &clr).await,
+				};
+				let bullet = Bullet::fire(public_id.clone(), &from, &config().gameplay.blaster);
+				let bullet_id = Uuid::new_v4().as_simple().to_string();
+				let audience = interests
+					.read()
+					.await
+					.audience(&[&public_id], Some(place_of(&from)));
+				let fired = ServerMessage::BulletFired {
+					id: bullet_id.clone(),
+					shooter: public_id,
+					flight: bullet.flight.clone(),
+					expires: bullet.expires,
+				};
+				projectiles.write().await.bullets.insert(bullet_id, bullet);
+				broadcast_to(&fired, &audience, &clr).await;
+				return Ok(());
 			}
 
-			shoot_info.shooter = Some(public_id);
+			shoot_info.shooter = Some(public_id.clone());
 
 			shoot_info.victim = match shoot_info.victim.clone() {
 				None => None,
-				Some(mut victim) => match clr.get(&victim.id) {
-					None => None, //Malicious behavior
-					Some(player) => {
-						if player.state.read().await.trajectory.health == 0 {
+				Some(mut victim) => {
+					let checked = match clr.get(&victim.id) {
+						None => Err(format!("Unknown victim {}", victim.id)),
+						Some(_) if victim.id == public_id => Err("Shot at themselves".to_string()),
+						Some(player) => validate_hit(
+							&state.history,
+							&shoot_info,
+							&victim,
+							&player.state.read().await.history,
+							time_now,
+						)
+						.map(|_| player),
+					};
+					match checked {
+						Err(reason) => {
+							reject_shot(&public_id, reason, suspects, &clr).await?;
 							None
-						} else {
-							let victim_state = {
-								let mut victim_writer = player.state.write().await;
-								victim_writer.trajectory.apply_change(UpdateTypeWrapper {
-									utype: UpdateType::Bullet,
-									value: Some(25u8),
-								});
-								if victim_writer.trajectory.health == 0 {
-									victim_writer.trajectory.advance(time_now + 5000);
-									//so that loot is dropped there
-								}
-								victim_writer.clone()
-							};
-
-							if victim_state.trajectory.health == 0 {
-								let mut rng = StdRng::from_entropy();
-								let dropped_loot = LootObject {
-									x: victim_state.trajectory.pos.x,
-									y: victim_state.trajectory.pos.y,
-									loot: match rng.gen_range(0..101) {
-										0..=33 => LootContent::Cash(victim_state.cash / 2),
-										34..=66 => LootContent::PistolAmmo(15),
-										_ => LootContent::Health(30),
-									},
+						}
+						Ok(player) => {
+							if player.state.read().await.trajectory.health == 0 {
+								None
+							} else {
+								let victim_state = {
+									let mut victim_writer = player.state.write().await;
+									let hit = UpdateTypeWrapper {
+										utype: UpdateType::Bullet,
+										value: Some(config().gameplay.pistol_damage),
+									};
+									victim_writer.trajectory.apply_change(hit.clone());
+									victim_writer.record(Some(hit));
+									if victim_writer.trajectory.health == 0 {
+										victim_writer.trajectory.advance(time_now + 5000);
+										//so that loot is dropped there
+										victim_writer.death_announced = true; //the shot tells everyone
+									}
+									victim_writer.clone()
 								};
-								let dropped_loot_uuid = Uuid::new_v4().as_simple().to_string();
-								world_loot
-									.write()
-									.await
-									.insert(dropped_loot_uuid.clone(), dropped_loot.clone());
-								victim.loot = Some(LootDrop {
-									object: dropped_loot,
-									uuid: dropped_loot_uuid,
-								});
+
+								victim.loot = if victim_state.trajectory.health == 0 {
+									Some(drop_loot(&victim_state, world_loot).await)
+								} else {
+									None
+								};
+								Some(victim)
 							}
-							Some(victim)
 						}
 					}
-				},
+				}
 			};
 
-			broadcast(&ServerMessage::Shoot(shoot_info), &clr).await;
+			let mut subjects = vec![public_id.as_str()];
+			if let Some(victim) = &shoot_info.victim {
+				subjects.push(&victim.id);
+			}
+			let audience = interests.read().await.audience(&subjects, None);
+			broadcast_to(&ServerMessage::Shoot(shoot_info), &audience, &clr).await;
 		}
 		ClientMessage::ClaimLoot { loot_id } => {
 			let loot_thing = {
@@ -315,19 +382,20 @@ pub async fn handle_game_message(
 					let pp = {
 						let mut writable = sender_state.write().await;
 						writable.trajectory.advance(time_now); //this may be problematic
-						writable.trajectory.pos.clone()
+						writable.trajectory.pos
 					};
-					if (pp.y - loot_obj.y).powi(2) + (pp.x - loot_obj.x).powi(2)
-						> LOOT_RADIUS.powi(2)
-					{
+					let distance = ((pp.y.to_f32() - loot_obj.y).powi(2)
+						+ (pp.x.to_f32() - loot_obj.x).powi(2))
+					.sqrt();
+					if distance > config().gameplay.loot_radius {
 						if let Some(client) = clr.get(&public_id) {
-							client
-								.transmit(&ServerMessage::LootReject(loot_id), Some(public_id))
-								.await?;
+							client.transmit(&ServerMessage::LootReject(loot_id)).await?;
 						} else {
 							eprintln!("Weird, did not find client {} in clr", public_id);
 						}
-						return Err("Too far for loot claim".into());
+						eprintln!("Too far for loot claim");
+						let offence = Offence::FarLootClaim { distance };
+						return suspect(&public_id, offence, suspects, &clr).await;
 					}
 
 					{
@@ -347,24 +415,39 @@ pub async fn handle_game_message(
 								}
 							}
 							LootContent::Health(health) => {
-								pstate_writer.trajectory.apply_change(UpdateTypeWrapper {
+								let change = UpdateTypeWrapper {
 									utype: UpdateType::Health,
 									value: Some(health),
-								});
+								};
+								pstate_writer.trajectory.apply_change(change.clone());
+								pstate_writer.record(Some(change));
+							}
+							LootContent::Fuel(percent) => {
+								let change = UpdateTypeWrapper {
+									utype: UpdateType::Refuel,
+									value: Some(percent),
+								};
+								pstate_writer.trajectory.apply_change(change.clone());
+								pstate_writer.record(Some(change));
 							}
 							LootContent::SpeedBoost => {}
 						}
 					} //locks are released
 					println!("locks released");
 
-					broadcast(
+					let audience = interests
+						.read()
+						.await
+						.audience(&[&loot_id, &public_id], None);
+					broadcast_to(
 						&ServerMessage::LootCollected {
-							loot_id: loot_id,
+							loot_id,
 							collector: public_id,
 						},
+						&audience,
 						&clr,
 					)
-					.await; //broadcast that loot was collected
+					.await; //tell who could see the loot that it was collected
 				}
 				None => {
 					return Err(format!("Can't find requested lootobject: {}", loot_id).into());
@@ -373,16 +456,10 @@ pub async fn handle_game_message(
 		}
 		ClientMessage::Correct(id) => {
 			if let Some(other) = clr.get(&id) {
-				let correction = other.state.read().await.trajectory.to_b64();
+				let correction = other.state.read().await.trajectory.clone();
 				if let Some(sender) = clr.get(&public_id) {
 					sender
-						.transmit(
-							&ServerMessage::Correct {
-								id: id,
-								tr: correction,
-							},
-							Some(public_id),
-						)
+						.transmit(&ServerMessage::Correct { id, tr: correction })
 						.await?;
 				}
 			} else {
@@ -393,13 +470,533 @@ pub async fn handle_game_message(
 	Ok(())
 }
 
-pub async fn broadcast(
+//The server's game loop. Every tick the players are advanced to where no late input can change
+//them anymore, so all clients are told the same outcome. Ships touching is resolved here
+//instead of in step: both ships are corrected for everyone, then the contact is announced.
+//Players that crashed, were rammed to death or were left outside the dome die and drop loot.
+//Then what everyone can see is worked out again and each player is sent the part of the world
+//in view, so clients don't have to ask for it.
+pub async fn tick(clients: Clients, world_loot: WorldLoot, interests: Interests, rate: u64) {
+	let mut interval = tokio::time::interval(Duration::from_millis(1000 / rate));
+	//contacts are looked for up to here, ships that got past it are taken back to it
+	let mut resolved = current_time() - MAX_TIME_BEFORE;
+	loop {
+		interval.tick().await;
+		let clr = clients.read().await;
+		let ids: Vec<&String> = clr.keys().collect();
+		let mut states = join_all(clr.values().map(|client| client.state.write())).await;
+		let mut ships: Vec<(&mut Trajectory, &mut TrajectoryHistory)> = states
+			.iter_mut()
+			.map(|state| {
+				let state = &mut **state;
+				(&mut state.trajectory, &mut state.history)
+			})
+			.collect();
+		let horizon = current_time() - MAX_TIME_BEFORE;
+		let contacts = advance_together(&mut ships, resolved, horizon);
+		resolved = horizon;
+		for state in states.iter_mut() {
+			state.checkpoint();
+		}
+		let deaths = unannounced_deaths(
+			ids.iter()
+				.copied()
+				.zip(states.iter_mut().map(|state| &mut **state)),
+			&world_loot,
+		)
+		.await;
+		let touched: BTreeSet<usize> = contacts
+			.iter()
+			.flat_map(|contact| [contact.first, contact.second])
+			.collect();
+		let corrections: Vec<(&String, ServerMessage)> = touched
+			.into_iter()
+			.map(|index| {
+				let correction = ServerMessage::Correct {
+					id: ids[index].clone(),
+					tr: states[index].trajectory.clone(),
+				};
+				(ids[index], correction)
+			})
+			.collect();
+		let ships: Vec<(Spot, bool)> = states
+			.iter()
+			.zip(&ids)
+			.map(|(state, id)| {
+				let (x, y) = place_of(&state.trajectory);
+				let spot = Spot {
+					id: id.to_string(),
+					x,
+					y,
+				};
+				(spot, state.trajectory.health > 0)
+			})
+			.collect();
+		let living: HashMap<&String, PlayerState> = states
+			.iter()
+			.zip(&ids)
+			.filter(|(state, _)| state.trajectory.health > 0)
+			.map(|(state, id)| (*id, (**state).clone()))
+			.collect();
+		drop(states);
+		//told to whoever saw them until now
+		{
+			let interest = interests.read().await;
+			for (id, correction) in corrections {
+				broadcast_to(&correction, &interest.audience(&[id], None), &clr).await;
+			}
+			for contact in contacts {
+				println!(
+					"{} and {} collided for {} damage",
+					ids[contact.first], ids[contact.second], contact.damage
+				);
+				broadcast_to(
+					&ServerMessage::ShipContact {
+						first: ids[contact.first].clone(),
+						second: ids[contact.second].clone(),
+						damage: contact.damage,
+					},
+					&interest.audience(&[ids[contact.first], ids[contact.second]], None),
+					&clr,
+				)
+				.await;
+			}
+			for (from, loot) in deaths {
+				println!("{} died", from);
+				let audience = interest.audience(&[&from], None);
+				broadcast_to(
+					&ServerMessage::PlayerDeath {
+						loot: Some(loot),
+						from,
+					},
+					&audience,
+					&clr,
+				)
+				.await;
+			}
+		}
+		let loot = world_loot.read().await.clone();
+		let loot_spots = loot
+			.iter()
+			.map(|(loot_id, object)| Spot {
+				id: loot_id.clone(),
+				x: object.x,
+				y: object.y,
+			})
+			.collect();
+		let mut interest = interests.write().await;
+		let changes = interest.refresh(ships, loot_spots);
+		let interest = interest.downgrade();
+		for (public_id, client) in clr.iter() {
+			let view = interest.view(public_id).cloned().unwrap_or_default();
+			let mut messages = Vec::new();
+			if let Some(change) = changes.get(public_id) {
+				if !change.left.is_empty() {
+					messages.push(ServerMessage::LeaveView {
+						players: change.left.players.iter().cloned().collect(),
+						loot: change.left.loot.iter().cloned().collect(),
+					});
+				}
+				if !change.entered.is_empty() {
+					messages.push(ServerMessage::EnterView {
+						players: change
+							.entered
+							.players
+							.iter()
+							.filter_map(|id| living.get(id).map(|state| state.view(false)))
+							.collect(),
+						loot: in_view(&loot, &change.entered),
+					});
+				}
+			}
+			let seen: HashMap<String, u64> = living
+				.iter()
+				.filter(|(id, _)| **id == public_id || view.players.contains(**id))
+				.map(|(id, state)| (id.to_string(), state.history.revision()))
+				.collect();
+			let delta = client
+				.snapshots
+				.write()
+				.await
+				.next(seen, in_view(&loot, &view).into_keys().collect());
+			messages.push(ServerMessage::GameState {
+				sequence: delta.sequence,
+				base: delta.base,
+				players: delta
+					.players
+					.iter()
+					.filter_map(|id| living.get(id).map(|state| state.view(id == public_id)))
+					.collect(),
+				loot: delta
+					.loot
+					.into_iter()
+					.filter_map(|loot_id| {
+						loot.get(&loot_id).map(|object| (loot_id, object.clone()))
+					})
+					.collect(),
+				gone: delta.gone,
+				taken: delta.taken,
+			});
+			for message in messages {
+				if let Err(e) = client.transmit(&message).await {
+					eprintln!("Error transmitting message: {}", e);
+				}
+			}
+		}
+	}
+}
+
+//where something happened, for working out who can see it
+fn place_of(trajectory: &Trajectory) -> (f32, f32) {
+	(trajectory.pos.x.to_f32(), trajectory.pos.y.to_f32())
+}
+
+fn in_view(loot: &HashMap<String, LootObject>, view: &View) -> HashMap<String, LootObject> {
+	view.loot
+		.iter()
+		.filter_map(|loot_id| {
+			loot.get(loot_id)
+				.map(|object| (loot_id.clone(), object.clone()))
+		})
+		.collect()
+}
+
+//tells the shooter that a shot didn't count and why
+async fn reject_shot(
+	public_id: &String,
+	reason: String,
+	suspects: &Suspects,
+	clr: &tokio::sync::RwLockReadGuard<'_, HashMap<String, Client>>,
+) -> Result<(), Box<dyn Error>> {
+	eprintln!("Rejected shot by {}: {}", public_id, reason);
+	if let Some(client) = clr.get(public_id) {
+		client
+			.transmit(&ServerMessage::ShotRejected {
+				reason: reason.clone(),
+			})
+			.await?;
+	}
+	suspect(public_id, Offence::ImpossibleShot { reason }, suspects, clr).await
+}
+
+//Records what the player did. Warnings are sent right away, kicks and bans are up to the
+//connection, which checks after every message.
+async fn suspect(
+	public_id: &String,
+	offence: Offence,
+	suspects: &Suspects,
+	clr: &tokio::sync::RwLockReadGuard<'_, HashMap<String, Client>>,
+) -> Result<(), Box<dyn Error>> {
+	let action = suspects
+		.write()
+		.await
+		.report(public_id, offence, current_time());
+	match action {
+		Some(Action::Warn) => {
+			println!("Warning {}", public_id);
+			if let Some(client) = clr.get(public_id) {
+				let warning = ServerMessage::Warning(
+					"The server is rejecting a lot of what you send".to_string(),
+				);
+				client.transmit(&warning).await?;
+			}
+		}
+		Some(action) => println!("{:?} for {}", action, public_id),
+		None => {}
+	}
+	Ok(())
+}
+
+//The players that died since the others were last told, each with the loot it leaves. Ships
+//die wherever they are advanced: in the tick, but also catching up, in an update or a claim.
+async fn unannounced_deaths<'a>(
+	states: impl Iterator<Item = (&'a String, &'a mut PlayerState)>,
+	world_loot: &WorldLoot,
+) -> Vec<(String, LootDrop)> {
+	let mut deaths = Vec::new();
+	for (id, state) in states {
+		if state.announce_death() {
+			deaths.push((id.clone(), drop_loot(state, world_loot).await));
+		}
+	}
+	deaths
+}
+
+//leaves something behind where a player died
+async fn drop_loot(victim_state: &PlayerState, world_loot: &WorldLoot) -> LootDrop {
+	let mut rng = StdRng::from_entropy();
+	let drops = &config().gameplay.drops;
+	let dropped_loot = LootObject {
+		x: victim_state.trajectory.pos.x.to_f32(),
+		y: victim_state.trajectory.pos.y.to_f32(),
+		loot: match rng.gen_range(0..101) {
+			0..=25 => LootContent::Cash((victim_state.cash as f32 * drops.cash_share) as u32),
+			26..=50 => LootContent::PistolAmmo(drops.ammo),
+			51..=75 => LootContent::Fuel(drops.fuel),
+			_ => LootContent::Health(drops.health),
+		},
+	};
+	let dropped_loot_uuid = Uuid::new_v4().as_simple().to_string();
+	world_loot
+		.write()
+		.await
+		.insert(dropped_loot_uuid.clone(), dropped_loot.clone());
+	LootDrop {
+		object: dropped_loot,
+		uuid: dropped_loot_uuid,
+	}
+}
+
+//health lost to a projectile, a player it kills leaves loot
+async fn hurt(
+	public_id: &str,
+	state: &mut PlayerState,
+	damage: u8,
+	now: u64,
+	world_loot: &WorldLoot,
+) -> ProjectileHit {
+	let hit = UpdateTypeWrapper {
+		utype: UpdateType::Bullet,
+		value: Some(damage),
+	};
+	state.trajectory.apply_change(hit.clone());
+	state.record(Some(hit));
+	let loot = if state.trajectory.health == 0 {
+		state.trajectory.advance(now + 5000); //so that loot is dropped there
+		state.death_announced = true; //the hit tells everyone, with the loot
+		Some(drop_loot(state, world_loot).await)
+	} else {
+		None
+	};
+	ProjectileHit {
+		id: public_id.to_string(),
+		damage,
+		loot,
+	}
+}
+
+//Grenades are stepped on the server only, clients draw them from the flight they were sent
+//when thrown. Every ship alive is damaged by where it was when the grenade went off.
+pub async fn detonate_grenades(
+	clients: Clients,
+	projectiles: Projectiles,
+	world_loot: WorldLoot,
+	interests: Interests,
+) {
+	let mut interval = tokio::time::interval(Duration::from_millis(GRENADE_INTERVAL));
+	loop {
+		interval.tick().await;
+		let now = current_time();
+		let exploded: Vec<(String, Grenade)> = {
+			let grenades_writer = &mut projectiles.write().await.grenades;
+			let ids: Vec<String> = grenades_writer
+				.iter_mut()
+				.filter_map(|(id, grenade)| grenade.advance(now).map(|_| id.clone()))
+				.collect();
+			ids.iter()
+				.filter_map(|id| grenades_writer.remove_entry(id))
+				.collect()
+		};
+		if exploded.is_empty() {
+			continue;
+		}
+		let clr = clients.read().await;
+		for (grenade_id, grenade) in exploded {
+			let mut hits = Vec::new();
+			for (public_id, client) in clr.iter() {
+				let mut writer = client.state.write().await;
+				if writer.trajectory.health == 0 {
+					continue;
+				}
+				let target = writer
+					.trajectory_at(grenade.flight.time)
+					.unwrap_or_else(|| writer.trajectory.clone());
+				let damage = grenade.damage_to(&target);
+				if damage == 0 {
+					continue;
+				}
+				hits.push(hurt(public_id, &mut writer, damage, now, &world_loot).await);
+			}
+			println!(
+				"Grenade of {} went off hitting {} players",
+				grenade.thrower,
+				hits.len()
+			);
+			let mut subjects: Vec<&str> = hits.iter().map(|hit| hit.id.as_str()).collect();
+			subjects.push(&grenade.thrower);
+			let audience = interests
+				.read()
+				.await
+				.audience(&subjects, Some(place_of(&grenade.flight)));
+			broadcast_to(
+				&ServerMessage::GrenadeExploded {
+					id: grenade_id,
+					x: grenade.flight.pos.x.to_f32(),
+					y: grenade.flight.pos.y.to_f32(),
+					time: grenade.flight.time,
+					hits,
+				},
+				&audience,
+				&clr,
+			)
+			.await;
+		}
+	}
+}
+
+//Bullets are stepped on the server only, each together with where every other ship was at the
+//same time. The first ship a bullet sweeps through takes the damage.
+pub async fn fly_bullets(
+	clients: Clients,
+	projectiles: Projectiles,
+	world_loot: WorldLoot,
+	interests: Interests,
+) {
+	let mut interval = tokio::time::interval(Duration::from_millis(BULLET_INTERVAL));
+	loop {
+		interval.tick().await;
+		let now = current_time();
+		if projectiles.read().await.bullets.is_empty() {
+			continue;
+		}
+		//one look at every player per pass, no player is locked while the bullets fly
+		let histories: Vec<(String, TrajectoryHistory)> = {
+			let clr = clients.read().await;
+			let states = join_all(clr.values().map(|client| client.state.read())).await;
+			clr.keys()
+				.cloned()
+				.zip(states.iter().map(|state| state.history.clone()))
+				.collect()
+		};
+		let stopped: Vec<(String, Bullet, Option<String>)> = {
+			let bullets = &mut projectiles.write().await.bullets;
+			let mut impacts = Vec::new();
+			for (bullet_id, bullet) in bullets.iter_mut() {
+				let (ids, mut ships): (Vec<&String>, Vec<Trajectory>) = histories
+					.iter()
+					.filter(|(public_id, _)| *public_id != bullet.shooter)
+					.filter_map(|(public_id, history)| {
+						history.at(bullet.flight.time).map(|ship| (public_id, ship))
+					})
+					.unzip();
+				if let Some(impact) = bullet.advance(now, &mut ships) {
+					let victim = match impact {
+						Impact::Ship(index) => Some(ids[index].clone()),
+						Impact::Body | Impact::Expired => None,
+					};
+					impacts.push((bullet_id.clone(), victim));
+				}
+			}
+			impacts
+				.into_iter()
+				.filter_map(|(id, victim)| bullets.remove(&id).map(|bullet| (id, bullet, victim)))
+				.collect()
+		};
+		let clr = clients.read().await;
+		for (bullet_id, bullet, victim) in stopped {
+			let hit = match victim.and_then(|id| clr.get(&id).map(|client| (id, client))) {
+				Some((public_id, client)) => {
+					let mut writer = client.state.write().await;
+					if writer.trajectory.health == 0 {
+						None
+					} else {
+						Some(hurt(&public_id, &mut writer, bullet.damage, now, &world_loot).await)
+					}
+				}
+				None => None,
+			};
+			let mut subjects = vec![bullet.shooter.as_str()];
+			if let Some(hit) = &hit {
+				subjects.push(&hit.id);
+			}
+			let audience = interests
+				.read()
+				.await
+				.audience(&subjects, Some(place_of(&bullet.flight)));
+			broadcast_to(
+				&ServerMessage::BulletImpact {
+					id: bullet_id,
+					x: bullet.flight.pos.x.to_f32(),
+					y: bullet.flight.pos.y.to_f32(),
+					time: bullet.flight.time,
+					hit,
+				},
+				&audience,
+				&clr,
+			)
+			.await;
+		}
+	}
+}
+
+//sends to the players a message is of interest to, see Interest::audience
+pub async fn broadcast_to(
 	msg: &ServerMessage,
+	audience: &HashSet<String>,
 	clients_readlock: &tokio::sync::RwLockReadGuard<'_, HashMap<std::string::String, Client>>,
 ) {
-	for (public_id, client) in clients_readlock.iter() {
-		if let Err(e) = client.transmit(msg, Some(public_id.to_string())).await {
-			eprintln!("Error transmitting message: {}", e);
+	for public_id in audience {
+		if let Some(client) = clients_readlock.get(public_id) {
+			if let Err(e) = client.transmit(msg).await {
+				eprintln!("Error transmitting message: {}", e);
+			}
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::handler::default_state;
+	use std::sync::Arc;
+	use tokio::sync::RwLock;
+	use utils::map::{set_map, Map};
+	use utils::scalar::Scalar;
+
+	#[tokio::test]
+	async fn ships_that_crash_in_an_update_are_announced_once() {
+		set_map(Map::from_toml(include_str!("../../maps/default.toml")).unwrap());
+		let mut state = default_state();
+		//heading for the body right below
+		state.trajectory = Trajectory {
+			pos: Vector {
+				x: Scalar::from_f32(1123.0),
+				y: Scalar::from_f32(-4000.0),
+			},
+			vel: Vector {
+				x: Scalar::default(),
+				y: Scalar::from_f32(-300.0),
+			},
+			time: 1000,
+			health: 100,
+			..Trajectory::default()
+		};
+		state.history = TrajectoryHistory::new(&state.trajectory);
+		let mut crashed = state.trajectory.clone();
+		crashed.advance_to_time(5000);
+		assert_eq!(crashed.health, 0);
+		//dated after the crash, so the ship hits the body on its way to the update
+		let turn = UpdateTypeWrapper {
+			utype: UpdateType::RotCw,
+			value: None,
+		};
+		state
+			.trajectory
+			.update(turn, crashed.hash_str(), 5000, 5000)
+			.unwrap();
+
+		let id = "crashed".to_string();
+		let world_loot: WorldLoot = Arc::new(RwLock::new(HashMap::new()));
+		let deaths = unannounced_deaths([(&id, &mut state)].into_iter(), &world_loot).await;
+		assert_eq!(deaths.len(), 1);
+		assert_eq!(deaths[0].0, id);
+		assert!(world_loot.read().await.contains_key(&deaths[0].1.uuid));
+		//the next tick doesn't drop loot for it again
+		let again = unannounced_deaths([(&id, &mut state)].into_iter(), &world_loot).await;
+		assert!(again.is_empty());
+		assert_eq!(world_loot.read().await.len(), 1);
+		//but the next life is announced when it ends
+		spawn_from_prev(&mut state);
+		assert!(!state.death_announced);
+	}
+}
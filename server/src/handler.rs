@@ -1,17 +1,25 @@
-use crate::{ws, Client, Clients, Result};
+use crate::{config, ws, Client, Clients, Result};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{json, Value};
 use std::collections::HashMap;
 use std::fs;
+use std::net::IpAddr;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use uuid::Uuid;
 use warp::{http::StatusCode, reply::json, Reply};
 use xxhash_rust::xxh3::xxh3_64;
 
+use crate::Interests;
 use crate::PlayerState;
+use crate::Projectiles;
+use crate::Suspects;
 use crate::WorldLoot;
-use utils::server_gameobjects::*;
+use utils::delta::SnapshotLog;
+use utils::history::TrajectoryHistory;
+use utils::map::current_map;
+use utils::messages::*;
+use utils::protocol::Codec;
 use utils::trajectory::*;
 
 #[derive(Serialize, Debug)]
@@ -20,45 +28,64 @@ pub struct RegisterResponse {
 	private: String,
 }
 
+#[derive(Deserialize)]
+pub struct Handshake {
+	map: String,
+	#[serde(default)]
+	hashv: String, //missing on clients older than the versioned hash
+}
+
 #[derive(Deserialize)]
 pub struct UserSelections {
 	nick: String,
 	color: String,
 }
 
-pub async fn register_handler(body: Value, clients: Clients) -> Result<impl Reply> {
+pub async fn register_handler(
+	body: Value,
+	address: Option<IpAddr>,
+	clients: Clients,
+	suspects: Suspects,
+) -> Result<impl Reply> {
 	println!("{}", body);
+	if let Some(address) = address {
+		if suspects.read().await.is_banned(&address) {
+			eprintln!("Refused to register banned address {}", address);
+			return Ok(StatusCode::FORBIDDEN.into_response());
+		}
+	}
 	let selection_result = serde_json::from_value::<UserSelections>(body);
 	match selection_result {
 		Ok(selections) => {
 			if selections.nick.len() > 24 {
-				return Ok(json(&"meow")); //TODO
+				return Ok(json(&"meow").into_response()); //TODO
 			}
 
 			let private_uuid = Uuid::new_v4().as_simple().to_string();
 			let public_id = format!("{:x}", xxh3_64(private_uuid.as_bytes()));
 			println!("Registering client {}", private_uuid);
-			register_client(public_id.clone(), selections, clients).await;
+			register_client(public_id.clone(), selections, address, clients).await;
 			Ok(json(&RegisterResponse {
 				public: public_id,
 				private: private_uuid,
-			}))
+			})
+			.into_response())
 		}
-		Err(_) => Ok(json(&"meow")), //TODO write this in an acceptable manner
+		Err(_) => Ok(json(&"meow").into_response()), //TODO write this in an acceptable manner
 	}
 }
 
 pub async fn serve_page() -> Result<impl Reply> {
-	let html = fs::read_to_string("client/site.html").unwrap();
+	let html = fs::read_to_string(config().assets.join("site.html")).unwrap();
 	Ok(warp::reply::html(html))
 }
 
-fn default_state() -> PlayerState {
+pub fn default_state() -> PlayerState {
+	let trajectory = Trajectory::default();
 	PlayerState {
 		name: "".to_string(),
 		id: "".to_string(),
-		cash: 20,
-		fuel: 100,
+		cash: config().gameplay.starting_cash,
 		color: Color {
 			r: 255,
 			g: 255,
@@ -78,22 +105,29 @@ fn default_state() -> PlayerState {
 				(
 					1,
 					Weapon {
-						weptype: WeaponType::Grenade {
-							press_time: current_time() as f32,
-						},
+						weptype: WeaponType::Grenade { press_time: 0 },
 						ammo: 2,
 					},
 				),
+				(
+					2,
+					Weapon {
+						weptype: WeaponType::Blaster,
+						ammo: 20,
+					},
+				),
 			]),
 		},
-		trajectory: Trajectory::default(),
+		history: TrajectoryHistory::new(&trajectory),
+		trajectory,
+		death_announced: false,
 	}
 }
 
-pub fn spawn_with_select(selections: &UserSelections, public_id: &String) -> PlayerState {
+pub fn spawn_with_select(selections: &UserSelections, public_id: &str) -> PlayerState {
 	PlayerState {
 		name: selections.nick.clone(),
-		id: public_id.clone(),
+		id: public_id.to_string(),
 		color: match selections.color.as_str() {
 			"red" => Color { r: 255, g: 0, b: 0 },
 			"orange" => Color {
@@ -119,20 +153,32 @@ pub fn spawn_with_select(selections: &UserSelections, public_id: &String) -> Pla
 }
 
 pub fn spawn_from_prev(prev_state: &mut PlayerState) {
+	//the previous life stays in the history
+	let history = std::mem::take(&mut prev_state.history);
 	*prev_state = PlayerState {
 		name: prev_state.name.clone(),
 		id: prev_state.id.clone(),
 		color: prev_state.color.clone(),
+		history,
 		..default_state()
-	}
+	};
+	prev_state.record(None);
 }
 
-async fn register_client(public_id: String, selections: UserSelections, clients: Clients) {
+async fn register_client(
+	public_id: String,
+	selections: UserSelections,
+	address: Option<IpAddr>,
+	clients: Clients,
+) {
 	clients.write().await.insert(
 		public_id.clone(),
 		Client {
 			state: Arc::new(RwLock::new(spawn_with_select(&selections, &public_id))),
 			sender: None,
+			codec: Codec::default(),
+			snapshots: Arc::new(RwLock::new(SnapshotLog::default())),
+			address,
 		},
 	);
 	println!("inserted {}", public_id);
@@ -145,23 +191,121 @@ pub async fn unregister_handler(public_id: String, clients: Clients) -> Result<i
 	Ok(StatusCode::OK)
 }
 
+#[allow(clippy::too_many_arguments)] //one for each of warp's filters
 pub async fn ws_handler(
 	ws: warp::ws::Ws,
 	private_id: String,
+	handshake: Handshake,
+	protocols: Option<String>,
 	clients: Clients,
 	loot: WorldLoot,
+	projectiles: Projectiles,
+	interests: Interests,
+	suspects: Suspects,
 ) -> Result<impl Reply> {
 	let public_id = format!("{:x}", xxh3_64(private_id.as_bytes()));
 	println!("Received connection from {}", public_id);
-	let client = clients.read().await.get(&public_id).cloned();
-	match client {
-		Some(c) => {
-			Ok(ws.on_upgrade(move |socket| {
-				ws::client_connection(socket, public_id, clients, loot, c)
-			}))
+	if handshake.hashv != hash_version() {
+		eprintln!(
+			"{} has hash version {:?} but server has {}",
+			public_id,
+			handshake.hashv,
+			hash_version()
+		);
+		return Ok(
+			warp::reply::with_status("Incompatible client", StatusCode::CONFLICT).into_response(),
+		);
+	}
+	let map_hash = current_map().hash_str();
+	if handshake.map != map_hash {
+		eprintln!(
+			"{} has map {} but server has {}",
+			public_id, handshake.map, map_hash
+		);
+		return Ok(warp::reply::with_status("Map mismatch", StatusCode::CONFLICT).into_response());
+	}
+	//clients that don't offer any subprotocol are spoken to in JSON
+	let codec = match protocols.as_deref().map(Codec::negotiate) {
+		None => Codec::Json,
+		Some(Some(codec)) => codec,
+		Some(None) => {
+			eprintln!(
+				"{} speaks none of the protocols here: {:?}",
+				public_id, protocols
+			);
+			return Ok(
+				warp::reply::with_status("Unsupported protocol", StatusCode::CONFLICT)
+					.into_response(),
+			);
 		}
-		None => Err(warp::reject::not_found()),
+	};
+	if !clients.read().await.contains_key(&public_id) {
+		return Err(warp::reject::not_found());
 	}
+	//larger frames are refused before they are read in
+	let max_frame = config().limits.max_frame;
+	let ws = ws.max_message_size(max_frame).max_frame_size(max_frame);
+	let upgrade = ws.on_upgrade(move |socket| {
+		ws::client_connection(
+			socket,
+			public_id,
+			codec,
+			clients,
+			loot,
+			projectiles,
+			interests,
+			suspects,
+		)
+	});
+	if protocols.is_none() {
+		return Ok(upgrade.into_response());
+	}
+	Ok(warp::reply::with_header(upgrade, "sec-websocket-protocol", codec.name()).into_response())
+}
+
+//The players suspected of cheating and the banned addresses, for whoever has the admin token.
+//Without a token set the report isn't served at all.
+pub async fn suspects_handler(
+	authorization: Option<String>,
+	token: Option<String>,
+	clients: Clients,
+	suspects: Suspects,
+) -> Result<impl Reply> {
+	let token = token.ok_or_else(warp::reject::not_found)?;
+	if authorization != Some(format!("Bearer {}", token)) {
+		return Ok(StatusCode::UNAUTHORIZED.into_response());
+	}
+	let suspects = suspects.read().await;
+	let clr = clients.read().await;
+	let mut players = Vec::new();
+	for flagged in suspects.flagged(current_time()) {
+		let name = match clr.get(&flagged.id) {
+			Some(client) => Some(client.state.read().await.name.clone()),
+			None => None, //left
+		};
+		players.push(json!({
+			"name": name,
+			"player": flagged,
+		}));
+	}
+	let banned: Vec<_> = suspects.banned().collect();
+	Ok(json(&json!({
+		"players": players,
+		"banned": banned,
+	}))
+	.into_response())
+}
+
+pub async fn map_handler() -> Result<impl Reply> {
+	Ok(warp::reply::with_header(
+		current_map().to_json(),
+		"content-type",
+		"application/json",
+	))
+}
+
+pub async fn gameplay_handler() -> Result<impl Reply> {
+	Ok(json(&config().gameplay))
 }
 
 pub async fn health_handler() -> Result<impl Reply> {
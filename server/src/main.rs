@@ -1,33 +1,89 @@
 use std::collections::HashMap;
 use std::convert::Infallible;
 use std::env;
-use std::sync::Arc;
+use std::net::{IpAddr, SocketAddr};
+use std::process;
+use std::sync::{Arc, OnceLock};
 use tokio::sync::{mpsc, RwLock};
 use warp::{ws::Message, Filter, Rejection};
 
-//use utils::server_gameobjects::LootContent;
+//use utils::messages::LootContent;
 //use uuid::Uuid;
 
+mod config;
 mod game;
 mod handler;
 mod ws;
 
-use utils::server_gameobjects::{LootObject, PlayerState};
+use utils::bullet::Bullet;
+use utils::conduct::Watchlist;
+use utils::delta::SnapshotLog;
+use utils::grenade::Grenade;
+use utils::interest::Interest;
+use utils::map::{set_map, Map};
+use utils::messages::LootObject;
+use utils::protocol::Codec;
+use utils::server_gameobjects::PlayerState;
+
+use config::{Config, HELP};
 
 type Result<T> = std::result::Result<T, Rejection>;
 type Clients = Arc<RwLock<HashMap<String, Client>>>;
 type WorldLoot = Arc<RwLock<HashMap<String, LootObject>>>;
+type Projectiles = Arc<RwLock<InFlight>>;
+type Interests = Arc<RwLock<Interest>>;
+type Suspects = Arc<RwLock<Watchlist>>;
 
 #[derive(Debug, Clone)]
 pub struct Client {
 	pub state: Arc<RwLock<PlayerState>>,
 	pub sender: Option<mpsc::UnboundedSender<std::result::Result<Message, warp::Error>>>,
+	pub codec: Codec,                        //agreed on when the websocket connected
+	pub snapshots: Arc<RwLock<SnapshotLog>>, //what it was sent of the world
+	pub address: Option<IpAddr>,             //it registered from
+}
+
+//how the server was set up, read once when it starts
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+pub fn config() -> &'static Config {
+	CONFIG.get_or_init(Config::default)
+}
+
+//everything that was shot and is still flying, by id
+#[derive(Debug, Default)]
+pub struct InFlight {
+	pub grenades: HashMap<String, Grenade>,
+	pub bullets: HashMap<String, Bullet>,
 }
 
 #[tokio::main]
 async fn main() {
+	let args: Vec<String> = env::args().skip(1).collect();
+	if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+		print!("{}", HELP);
+		return;
+	}
+	let loaded = Config::load(&args).unwrap_or_else(|e| fail(&e));
+	let config = CONFIG.get_or_init(|| loaded);
+	let mut map = Map::from_file(&config.map).unwrap_or_else(|e| fail(&e));
+	if let Some(integrator) = config.integrator {
+		map.integrator = integrator;
+	}
+	println!(
+		"Loaded map {} ({}), integrating with {}",
+		config.map.display(),
+		map.hash_str(),
+		map.integrator.name()
+	);
+	set_map(map);
+
 	let clients: Clients = Arc::new(RwLock::new(HashMap::new()));
 	let world_loot: WorldLoot = Arc::new(RwLock::new(HashMap::new()));
+	let projectiles: Projectiles = Arc::new(RwLock::new(InFlight::default()));
+	let interests: Interests = Arc::new(RwLock::new(Interest::default()));
+	let suspects: Suspects = Arc::new(RwLock::new(Watchlist::new(config.conduct.clone())));
+	let admin_token = config.admin_token.clone();
 	//	{
 	//		let mut loot_writer = world_loot.write().await;
 	//		for x in -150..150 {
@@ -42,13 +98,54 @@ async fn main() {
 	//		}
 	//	}
 
+	println!("Ticking {} times a second", config.tick_rate);
+
+	tokio::spawn(game::tick(
+		clients.clone(),
+		world_loot.clone(),
+		interests.clone(),
+		config.tick_rate,
+	));
+	tokio::spawn(game::detonate_grenades(
+		clients.clone(),
+		projectiles.clone(),
+		world_loot.clone(),
+		interests.clone(),
+	));
+	tokio::spawn(game::fly_bullets(
+		clients.clone(),
+		projectiles.clone(),
+		world_loot.clone(),
+		interests.clone(),
+	));
+
 	let health_route = warp::path!("health").and_then(handler::health_handler);
 
+	let map_route = warp::path!("map").and_then(handler::map_handler);
+
+	let gameplay_route = warp::path!("gameplay").and_then(handler::gameplay_handler);
+
+	let suspects_route = warp::path!("admin" / "suspects")
+		.and(warp::get())
+		.and(warp::header::optional::<String>("authorization"))
+		.and(warp::any().map(move || admin_token.clone()))
+		.and(with_clients(clients.clone()))
+		.and(with_suspects(suspects.clone()))
+		.and_then(handler::suspects_handler);
+
+	let address = warp::addr::remote()
+		.and(warp::header::optional::<String>("x-forwarded-for"))
+		.map(move |peer: Option<SocketAddr>, forwarded: Option<String>| {
+			config.client_address(peer.map(|peer| peer.ip()), forwarded.as_deref())
+		});
+
 	let register = warp::path("register");
 	let register_routes = register
 		.and(warp::post())
 		.and(warp::body::json())
+		.and(address)
 		.and(with_clients(clients.clone()))
+		.and(with_suspects(suspects.clone()))
 		.and_then(handler::register_handler)
 		.or(register
 			.and(warp::delete())
@@ -60,33 +157,44 @@ async fn main() {
 		.and(warp::get())
 		.and_then(handler::serve_page);
 
-	let assets = warp::path("static").and(warp::fs::dir("client"));
+	let assets = warp::path("static").and(warp::fs::dir(config.assets.clone()));
 
 	let ws_route = warp::path("ws")
 		.and(warp::ws())
 		.and(warp::path::param())
+		.and(warp::query::<handler::Handshake>())
+		.and(warp::header::optional::<String>("sec-websocket-protocol"))
 		.and(with_clients(clients.clone()))
 		.and(with_loot(world_loot.clone()))
+		.and(with_projectiles(projectiles.clone()))
+		.and(with_interests(interests.clone()))
+		.and(with_suspects(suspects.clone()))
 		.and_then(handler::ws_handler);
 
 	let routes = health_route
+		.or(map_route)
+		.or(gameplay_route)
+		.or(suspects_route)
 		.or(register_routes)
 		.or(ws_route)
 		.or(index)
 		.or(assets)
 		.with(warp::cors().allow_any_origin());
 
-	const USAGE: &str = "Usage: ./binary <port>";
-	let port: u16 = env::args().nth(1).expect(USAGE).parse().expect(USAGE);
-
 	warp::serve(routes)
 		//.tls()
 		//.cert_path("cert.pem")
 		//.key_path("key.rsa")
-		.run(([0, 0, 0, 0], port))
+		.run((config.address, config.port))
 		.await;
 }
 
+//a configuration the server can't start with
+fn fail(error: &str) -> ! {
+	eprintln!("{}", error);
+	process::exit(2);
+}
+
 fn with_clients(clients: Clients) -> impl Filter<Extract = (Clients,), Error = Infallible> + Clone {
 	warp::any().map(move || clients.clone())
 }
@@ -94,3 +202,21 @@ fn with_clients(clients: Clients) -> impl Filter<Extract = (Clients,), Error = I
 fn with_loot(loot: WorldLoot) -> impl Filter<Extract = (WorldLoot,), Error = Infallible> + Clone {
 	warp::any().map(move || loot.clone())
 }
+
+fn with_projectiles(
+	projectiles: Projectiles,
+) -> impl Filter<Extract = (Projectiles,), Error = Infallible> + Clone {
+	warp::any().map(move || projectiles.clone())
+}
+
+fn with_interests(
+	interests: Interests,
+) -> impl Filter<Extract = (Interests,), Error = Infallible> + Clone {
+	warp::any().map(move || interests.clone())
+}
+
+fn with_suspects(
+	suspects: Suspects,
+) -> impl Filter<Extract = (Suspects,), Error = Infallible> + Clone {
+	warp::any().map(move || suspects.clone())
+}
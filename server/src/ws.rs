@@ -1,20 +1,32 @@
-use crate::{Client, Clients};
+use crate::Clients;
 use futures::{FutureExt, StreamExt};
+use std::error::Error;
 use tokio::sync::mpsc;
 use tokio_stream::wrappers::UnboundedReceiverStream;
 use warp::ws::{Message, WebSocket};
 
-use crate::game::broadcast;
+use crate::game::broadcast_to;
 use crate::game::handle_game_message;
-use crate::WorldLoot;
-use utils::server_gameobjects::ServerMessage;
+use crate::{config, Interests, Projectiles, Suspects, WorldLoot};
+use utils::conduct::{Action, Offence};
+use utils::limits::{Limiter, Verdict};
+use utils::messages::{ClientMessage, ServerMessage};
+use utils::protocol::{from_binary, Codec};
+use utils::trajectory::current_time;
 
+const POLICY_VIOLATION: u16 = 1008; //close code for clients going over their limits
+const MESSAGE_TOO_BIG: u16 = 1009;
+
+#[allow(clippy::too_many_arguments)] //the shared state is passed piece by piece
 pub async fn client_connection(
 	ws: WebSocket,
 	public_id: String,
+	codec: Codec,
 	clients: Clients,
 	loot: WorldLoot,
-	client: Client,
+	projectiles: Projectiles,
+	interests: Interests,
+	suspects: Suspects,
 ) {
 	let (client_ws_sender, mut client_ws_rcv) = ws.split();
 	let (client_sender, client_rcv) = mpsc::unbounded_channel();
@@ -26,26 +38,27 @@ pub async fn client_connection(
 		}
 	}));
 
-	{
-		let clr = clients.read().await;
-		broadcast(
-			&ServerMessage::PlayerJoin(client.state.read().await.clone()),
-			&clr,
-		)
-		.await;
+	//the others are told about the player when it comes into their view
+	if let Some(client) = clients.write().await.get_mut(&public_id) {
+		client.sender = Some(client_sender.clone());
+		client.codec = codec;
 	}
-	clients
-		.write()
-		.await
-		.get_mut(&public_id.clone())
-		.unwrap()
-		.sender = Some(client_sender);
+	let mut limiter = Limiter::new(&config().limits.paced(config().tick_rate), current_time());
 
-	println!("{} connected", public_id);
+	println!("{} connected speaking {}", public_id, codec.name());
 
 	while let Some(result) = client_ws_rcv.next().await {
 		let msg = match result {
 			Ok(msg) => msg,
+			Err(e) if too_big(&e) => {
+				let reason = format!("Frames are limited to {} bytes", config().limits.max_frame);
+				eprintln!(
+					"Disconnecting {} ({}): {}",
+					public_id, MESSAGE_TOO_BIG, reason
+				);
+				let _ = client_sender.send(Ok(Message::close_with(MESSAGE_TOO_BIG, reason)));
+				break;
+			}
 			Err(e) => {
 				eprintln!(
 					"error receiving ws message for id: {}): {}",
@@ -56,30 +69,138 @@ pub async fn client_connection(
 			}
 		};
 
-		//TODO make client_msg with a rate limiter or cheat detection, exit this loop if triggered
-		client_msg(&public_id, msg, &clients, &loot).await;
+		let handled = client_msg(
+			&public_id,
+			msg,
+			&mut limiter,
+			&clients,
+			&loot,
+			&projectiles,
+			&interests,
+			&suspects,
+		)
+		.await;
+		if let Err(close) = handled {
+			if let Some((code, reason)) = close.close_frame() {
+				eprintln!("Disconnecting {} ({}): {}", public_id, code, reason);
+			}
+			let _ = client_sender.send(Ok(close));
+			break;
+		}
+	}
+	if !limiter.hits().is_empty() {
+		println!("{} went over its limits: {:?}", public_id, limiter.hits());
 	}
 
 	{
 		let clr = clients.read().await;
-		broadcast(&ServerMessage::PlayerLeave(public_id.clone()), &clr).await;
+		let audience = interests.read().await.audience(&[&public_id], None);
+		broadcast_to(
+			&ServerMessage::PlayerLeave(public_id.clone()),
+			&audience,
+			&clr,
+		)
+		.await;
 	}
 	clients.write().await.remove(&public_id);
 	println!("{} disconnected", public_id);
 }
 
-async fn client_msg(public_id: &String, msg: Message, clients: &Clients, loot: &WorldLoot) {
-	println!("received message from {}: {:?}", public_id, msg);
-	let message = match msg.to_str() {
-		Ok(v) => v,
-		Err(_) => {
-			eprintln!("msg.to_str failed");
-			return;
+//whether the client sent a frame over max_frame, which warp refuses
+fn too_big(error: &warp::Error) -> bool {
+	let cause = error
+		.source()
+		.and_then(|e| e.downcast_ref::<tungstenite::Error>());
+	matches!(cause, Some(tungstenite::Error::Capacity(_)))
+}
+
+//handles a frame from the client, or returns the close frame it is to be disconnected with
+#[allow(clippy::too_many_arguments)]
+async fn client_msg(
+	public_id: &String,
+	msg: Message,
+	limiter: &mut Limiter,
+	clients: &Clients,
+	loot: &WorldLoot,
+	projectiles: &Projectiles,
+	interests: &Interests,
+	suspects: &Suspects,
+) -> Result<(), Message> {
+	if !msg.is_text() && !msg.is_binary() {
+		return Ok(()); //pings, pongs and close frames are handled by warp
+	}
+	//either codec is accepted whatever was agreed on, each frame says which it is
+	let decoded: Result<ClientMessage, String> = match msg.to_str() {
+		Ok(text) => serde_json::from_str(text).map_err(|e| e.to_string()),
+		Err(_) => from_binary(msg.as_bytes()),
+	};
+	//frames that can't be read still count towards the total
+	let name = decoded.as_ref().map_or("Invalid", |message| message.name());
+	let verdict = limiter.allow(name, current_time());
+	if verdict != Verdict::Allowed {
+		let offence = Offence::Flooding {
+			message: name.to_string(),
+		};
+		suspects
+			.write()
+			.await
+			.report(public_id, offence, current_time());
+	}
+	match verdict {
+		Verdict::Allowed => {}
+		Verdict::Limited => {
+			eprintln!(
+				"{} went over a limit sending {}, so far {:?}",
+				public_id,
+				name,
+				limiter.hits()
+			);
+			//flooding alone can get a client kicked or banned
+			return judge(public_id, clients, suspects).await;
+		}
+		Verdict::Disconnect => {
+			return Err(Message::close_with(POLICY_VIOLATION, "Too many messages"));
+		}
+	}
+	let message = match decoded {
+		Ok(message) => message,
+		Err(e) => {
+			eprintln!("Can't deserialize message: {}", e);
+			return Ok(());
 		}
 	};
 
-	if let Err(e) = handle_game_message(public_id.clone(), message, clients, loot).await {
+	if let Err(e) = handle_game_message(
+		public_id.clone(),
+		message,
+		clients,
+		loot,
+		projectiles,
+		interests,
+		suspects,
+	)
+	.await
+	{
 		eprintln!("Error handling game message: {}", e);
 	}
-	println!("exit handler {}", public_id);
+	judge(public_id, clients, suspects).await
+}
+
+//acts on what the player is suspected of, returning the close frame if it is to go
+async fn judge(public_id: &str, clients: &Clients, suspects: &Suspects) -> Result<(), Message> {
+	let action = suspects.read().await.action(public_id, current_time());
+	match action {
+		Some(Action::Ban) => {
+			let address = match clients.read().await.get(public_id) {
+				Some(client) => client.address,
+				None => None,
+			};
+			if let Some(address) = address {
+				suspects.write().await.ban(address);
+			}
+			Err(Message::close_with(POLICY_VIOLATION, "Banned for cheating"))
+		}
+		Some(Action::Kick) => Err(Message::close_with(POLICY_VIOLATION, "Kicked for cheating")),
+		_ => Ok(()),
+	}
 }
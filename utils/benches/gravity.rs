@@ -0,0 +1,111 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use utils::map::{current_map, set_map, Map};
+use utils::scalar::{Real, Scalar};
+use utils::trajectory::{Body, Trajectory, Vector};
+
+//random static bodies spread so that their density stays close to the default map's
+fn random_map(num_bodies: usize) -> Map {
+	let mut rng = SmallRng::seed_from_u64(num_bodies as u64);
+	let dome_radius = 1200.0 * (num_bodies as f32).sqrt();
+	let bodies: Vec<Body> = (0..num_bodies)
+		.map(|_| Body {
+			pos: Vector {
+				x: Scalar::from_f32(rng.gen_range(-dome_radius..dome_radius)),
+				y: Scalar::from_f32(rng.gen_range(-dome_radius..dome_radius)),
+			},
+			radius: Scalar::from_f32(rng.gen_range(50f32..150f32)),
+			orbit: None,
+			outline: None,
+		})
+		.collect();
+	let data = serde_json::json!({ "dome_radius": dome_radius, "bodies": bodies });
+	Map::from_json(&data.to_string()).unwrap()
+}
+
+fn random_positions(map: &Map, count: usize) -> Vec<Vector> {
+	let mut rng = SmallRng::seed_from_u64(0);
+	let reach = map.dome_radius.to_f32() / 2.0;
+	(0..count)
+		.map(|_| Vector {
+			x: Scalar::from_f32(rng.gen_range(-reach..reach)),
+			y: Scalar::from_f32(rng.gen_range(-reach..reach)),
+		})
+		.collect()
+}
+
+fn pull(c: &mut Criterion) {
+	let mut group = c.benchmark_group("pull");
+	for num_bodies in [25, 100, 400, 1600] {
+		let map = random_map(num_bodies);
+		let positions = random_positions(&map, 64);
+		//fill the cells outside the measurement
+		for pos in &positions {
+			Trajectory::pull_at(&map, &map.bodies, pos);
+		}
+		group.bench_with_input(BenchmarkId::new("sum", num_bodies), &map, |b, map| {
+			b.iter(|| {
+				for pos in &positions {
+					black_box(Trajectory::pull_sum(&map.bodies, pos));
+				}
+			})
+		});
+		group.bench_with_input(BenchmarkId::new("grid", num_bodies), &map, |b, map| {
+			b.iter(|| {
+				for pos in &positions {
+					black_box(Trajectory::pull_at(map, &map.bodies, pos));
+				}
+			})
+		});
+	}
+	group.finish();
+}
+
+//first of a fixed set of starting points where a ship survives the whole replay
+fn surviving_start(steps: u32) -> Trajectory {
+	let mut rng = SmallRng::seed_from_u64(1);
+	let reach = current_map().dome_radius.to_f32() / 2.0;
+	loop {
+		let start = Trajectory {
+			propelling: true,
+			pos: Vector {
+				x: Scalar::from_f32(rng.gen_range(-reach..reach)),
+				y: Scalar::from_f32(rng.gen_range(-reach..reach)),
+			},
+			vel: Vector::default(),
+			spin: Scalar::default(),
+			spin_direction: 1,
+			time: 0,
+			health: 100,
+			boosters: 1,
+			fuel: u16::MAX, //propels for the whole replay
+		};
+		let mut trajectory = start.clone();
+		if (0..steps).all(|_| trajectory.step() && trajectory.health > 0) {
+			return start;
+		}
+	}
+}
+
+//replaying history the way StateQuery and advance_to_min_time do
+fn replay(c: &mut Criterion) {
+	let mut group = c.benchmark_group("replay_1000_steps");
+	for num_bodies in [25, 400, 1600] {
+		set_map(random_map(num_bodies));
+		let start = surviving_start(1000);
+		group.bench_function(BenchmarkId::from_parameter(num_bodies), |b| {
+			b.iter(|| {
+				let mut trajectory = start.clone();
+				for _ in 0..1000 {
+					trajectory.step();
+				}
+				black_box(trajectory)
+			})
+		});
+	}
+	group.finish();
+}
+
+criterion_group!(benches, pull, replay);
+criterion_main!(benches);
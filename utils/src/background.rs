@@ -11,7 +11,8 @@ use rand::SeedableRng;
 
 use kdbush::KDBush;
 
-use crate::trajectory::DOME_RADIUS;
+use crate::map::current_map;
+use crate::scalar::Real;
 
 #[wasm_bindgen]
 extern "C" {
@@ -47,9 +48,10 @@ pub struct World {
 impl World {
 	#[wasm_bindgen(constructor)]
 	pub fn new() -> Self {
-		let normal = Normal::new(0f64, (DOME_RADIUS / 12f32) as f64).unwrap();
+		let dome_radius = current_map().dome_radius.to_f32();
+		let normal = Normal::new(0f64, (dome_radius / 12f32) as f64).unwrap();
 		let mut rng = SmallRng::seed_from_u64(122);
-		let points: Vec<(f64, f64)> = (1..((DOME_RADIUS as u64).pow(2) / 3600))
+		let points: Vec<(f64, f64)> = (1..((dome_radius as u64).pow(2) / 3600))
 			.map(|_| (normal.sample(&mut rng), normal.sample(&mut rng)))
 			.collect();
 		let gg = KDBush::create(points.clone(), kdbush::DEFAULT_NODE_SIZE);
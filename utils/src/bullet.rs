@@ -0,0 +1,101 @@
+use serde::{Deserialize, Serialize};
+
+use crate::scalar::{Real, Scalar};
+use crate::trajectory::{Trajectory, Vector, PLAYER_RADIUS};
+
+//A blaster bolt. It leaves the nose with the shooter's velocity added, is bent by gravity like
+//a ship with its engine off and is absorbed by any body it touches, so shots can be curved
+//around planets. Ships are tested along the whole step so a fast bolt can't jump over one.
+
+//how the blaster fires, the server's settings can change it
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Blaster {
+	pub muzzle_speed: f32, //relative to the shooter
+	pub lifetime: u64,     //millis until a bolt that hit nothing fades
+	pub damage: u8,
+}
+
+impl Default for Blaster {
+	fn default() -> Blaster {
+		Blaster {
+			muzzle_speed: 900.0,
+			lifetime: 4000,
+			damage: 20,
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct Bullet {
+	pub shooter: String,
+	pub flight: Trajectory,
+	pub expires: u64,
+	pub damage: u8, //to the ship it hits
+}
+
+//what stopped a bullet
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Impact {
+	Ship(usize), //index into the ships given to advance
+	Body,
+	Expired,
+}
+
+impl Bullet {
+	pub fn fire(shooter: String, from: &Trajectory, blaster: &Blaster) -> Bullet {
+		Bullet {
+			shooter,
+			flight: from.launched(Scalar::from_f32(blaster.muzzle_speed)),
+			expires: from.time + blaster.lifetime,
+			damage: blaster.damage,
+		}
+	}
+
+	//Steps the bullet up to time together with the ships it could hit, which have to start at
+	//the bullet's time. Returns what stopped it, it doesn't move after that.
+	pub fn advance(&mut self, time: u64, ships: &mut [Trajectory]) -> Option<Impact> {
+		while self.flight.time < time.min(self.expires) {
+			let start = self.flight.pos;
+			let ship_starts: Vec<Vector> = ships.iter().map(|ship| ship.pos).collect();
+			self.flight.step();
+			if self.flight.health == 0 {
+				return Some(Impact::Body);
+			}
+			for (index, ship) in ships.iter_mut().enumerate() {
+				if !ship.step() {
+					continue; //dead
+				}
+				if sweep_hits(&start, &self.flight.pos, &ship_starts[index], &ship.pos) {
+					return Some(Impact::Ship(index));
+				}
+			}
+		}
+		if self.flight.time >= self.expires {
+			return Some(Impact::Expired);
+		}
+		None
+	}
+}
+
+//whether the bolt comes within a ship's radius of it while both move along their steps
+fn sweep_hits(
+	bolt_start: &Vector,
+	bolt_end: &Vector,
+	ship_start: &Vector,
+	ship_end: &Vector,
+) -> bool {
+	//relative to the ship, so that only the bolt moves
+	let x0 = (bolt_start.x - ship_start.x).to_f32();
+	let y0 = (bolt_start.y - ship_start.y).to_f32();
+	let dx = (bolt_end.x - ship_end.x).to_f32() - x0;
+	let dy = (bolt_end.y - ship_end.y).to_f32() - y0;
+	let length_sq = dx * dx + dy * dy;
+	let t = if length_sq == 0.0 {
+		0.0
+	} else {
+		(-(x0 * dx + y0 * dy) / length_sq).clamp(0.0, 1.0)
+	};
+	let (cx, cy) = (x0 + dx * t, y0 + dy * t);
+	cx * cx + cy * cy < PLAYER_RADIUS * PLAYER_RADIUS
+}
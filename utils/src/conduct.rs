@@ -0,0 +1,219 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::IpAddr;
+
+use serde::{Deserialize, Serialize};
+
+//What players did that a fair client wouldn't. Every offence adds to the player's suspicion,
+//which halves every half life so that the odd rejection a laggy connection causes is
+//forgotten, while a player that keeps at it climbs through the thresholds: warned, then
+//shadow muted (shots and loot claims are dropped without telling it), then kicked and at
+//last banned, which keeps its address from registering again.
+
+const RECENT: usize = 20; //offences kept per player for the report
+const FORGOTTEN: f32 = 0.1; //players with less suspicion than this are dropped
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind")]
+pub enum Offence {
+	RejectedUpdate { reason: String },
+	UnaffordablePurchase { cost: u32, cash: u32 },
+	ImpossibleShot { reason: String },
+	FarLootClaim { distance: f32 },
+	DeadCommand { message: String }, //or spawning while alive
+	Flooding { message: String },
+}
+
+impl Offence {
+	pub fn name(&self) -> &'static str {
+		match self {
+			Offence::RejectedUpdate { .. } => "RejectedUpdate",
+			Offence::UnaffordablePurchase { .. } => "UnaffordablePurchase",
+			Offence::ImpossibleShot { .. } => "ImpossibleShot",
+			Offence::FarLootClaim { .. } => "FarLootClaim",
+			Offence::DeadCommand { .. } => "DeadCommand",
+			Offence::Flooding { .. } => "Flooding",
+		}
+	}
+
+	//how much it adds, a lagging client has the odd update or shot rejected but a fair one never
+	//buys what it can't afford
+	fn weight(&self) -> f32 {
+		match self {
+			Offence::RejectedUpdate { .. } | Offence::DeadCommand { .. } => 1.0,
+			Offence::ImpossibleShot { .. } | Offence::FarLootClaim { .. } => 2.0,
+			Offence::Flooding { .. } => 2.0,
+			Offence::UnaffordablePurchase { .. } => 5.0,
+		}
+	}
+}
+
+//what is done about a player, in order of severity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Action {
+	Warn,
+	ShadowMute,
+	Kick,
+	Ban,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Policy {
+	pub half_life: u64,                 //millis for suspicion to halve
+	pub thresholds: Vec<(f32, Action)>, //suspicion from which each action is taken
+}
+
+impl Default for Policy {
+	fn default() -> Policy {
+		Policy {
+			half_life: 60000,
+			thresholds: vec![
+				(10.0, Action::Warn),
+				(25.0, Action::ShadowMute),
+				(50.0, Action::Kick),
+				(100.0, Action::Ban),
+			],
+		}
+	}
+}
+
+impl Policy {
+	pub fn validate(&self) -> Result<(), String> {
+		if self.half_life == 0 {
+			return Err("half_life has to be at least a millisecond".to_string());
+		}
+		match self
+			.thresholds
+			.iter()
+			.find(|(threshold, _)| threshold.is_nan() || *threshold <= 0.0)
+		{
+			Some((_, action)) => Err(format!("the threshold for {:?} has to be positive", action)),
+			None => Ok(()),
+		}
+	}
+
+	//the most severe action the suspicion calls for
+	fn level(&self, suspicion: f32) -> Option<Action> {
+		self.thresholds
+			.iter()
+			.filter(|(threshold, _)| suspicion >= *threshold)
+			.map(|(_, action)| *action)
+			.max()
+	}
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Event {
+	pub time: u64,
+	pub offence: Offence,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Record {
+	suspicion: f32,
+	time: u64,             //when suspicion was last worked out
+	acted: Option<Action>, //the most severe action taken since it was last below its threshold
+	counts: HashMap<&'static str, u32>,
+	recent: VecDeque<Event>,
+}
+
+impl Record {
+	fn suspicion(&self, half_life: u64, time: u64) -> f32 {
+		let elapsed = time.saturating_sub(self.time) as f32;
+		self.suspicion * 0.5f32.powf(elapsed / half_life as f32)
+	}
+}
+
+//an entry of the admin report
+#[derive(Debug, Clone, Serialize)]
+pub struct Flagged {
+	pub id: String,
+	pub suspicion: f32,
+	pub action: Action, //the most severe one it called for since it was last below it
+	pub counts: HashMap<&'static str, u32>,
+	pub recent: Vec<Event>,
+}
+
+#[derive(Debug, Default)]
+pub struct Watchlist {
+	policy: Policy,
+	records: HashMap<String, Record>, //by public id, also of players that left
+	banned: HashSet<IpAddr>,
+}
+
+impl Watchlist {
+	pub fn new(policy: Policy) -> Watchlist {
+		Watchlist {
+			policy,
+			..Watchlist::default()
+		}
+	}
+
+	//Records an offence at time. Returns the action to take if the player just became
+	//suspicious enough for a more severe one than before.
+	pub fn report(&mut self, player: &str, offence: Offence, time: u64) -> Option<Action> {
+		let half_life = self.policy.half_life;
+		self.records
+			.retain(|_, record| record.suspicion(half_life, time) >= FORGOTTEN);
+		let record = self.records.entry(player.to_string()).or_default();
+		let before = record.suspicion(half_life, time);
+		record.acted = record.acted.min(self.policy.level(before));
+		record.suspicion = before + offence.weight();
+		record.time = time;
+		*record.counts.entry(offence.name()).or_default() += 1;
+		record.recent.push_back(Event { time, offence });
+		if record.recent.len() > RECENT {
+			record.recent.pop_front();
+		}
+		let level = self.policy.level(record.suspicion);
+		if level <= record.acted {
+			return None;
+		}
+		record.acted = level;
+		level
+	}
+
+	//what the player's suspicion calls for now
+	pub fn action(&self, player: &str, time: u64) -> Option<Action> {
+		let record = self.records.get(player)?;
+		self.policy
+			.level(record.suspicion(self.policy.half_life, time))
+	}
+
+	pub fn muted(&self, player: &str, time: u64) -> bool {
+		self.action(player, time) >= Some(Action::ShadowMute)
+	}
+
+	pub fn ban(&mut self, address: IpAddr) {
+		self.banned.insert(address);
+	}
+
+	pub fn is_banned(&self, address: &IpAddr) -> bool {
+		self.banned.contains(address)
+	}
+
+	pub fn banned(&self) -> impl Iterator<Item = &IpAddr> {
+		self.banned.iter()
+	}
+
+	//the players suspicious enough for any action, most suspicious first
+	pub fn flagged(&self, time: u64) -> Vec<Flagged> {
+		let mut flagged: Vec<Flagged> = self
+			.records
+			.iter()
+			.filter_map(|(id, record)| {
+				let suspicion = record.suspicion(self.policy.half_life, time);
+				let action = self.policy.level(suspicion).max(record.acted);
+				action.map(|action| Flagged {
+					id: id.clone(),
+					suspicion,
+					action,
+					counts: record.counts.clone(),
+					recent: record.recent.iter().cloned().collect(),
+				})
+			})
+			.collect();
+		flagged.sort_by(|a, b| b.suspicion.total_cmp(&a.suspicion));
+		flagged
+	}
+}
@@ -0,0 +1,127 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+//Which part of the world a client still needs to be sent. Snapshots are numbered and clients
+//acknowledge the ones they applied. A new snapshot only lists what changed since the last
+//acknowledged one: players that came or went, loot that appeared or was taken, and players
+//whose trajectory changed other than by stepping, which the client can do by itself. A client
+//that acknowledged nothing yet, or fell too far behind, is sent everything again.
+//
+//Messages arrive in order, so a client applying a snapshot has applied every one before it.
+//The snapshots sent after the acknowledged one can have told it about things that are gone
+//again by now, so the changes are worked out against all of them and not just the last one
+//acknowledged.
+
+pub const MAX_BEHIND: u64 = 32; //unacknowledged snapshots before a client is sent everything again
+
+//what one snapshot told a client about: the players with the revision of their trajectory, and the loot
+#[derive(Debug, Clone, Default)]
+struct Contents {
+	players: HashMap<String, u64>,
+	loot: HashSet<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Delta {
+	pub sequence: u64,
+	pub base: Option<u64>, //the acknowledged snapshot it builds on, None when it lists everything
+	pub players: Vec<String>, //new ones and ones whose trajectory changed
+	pub gone: Vec<String>,
+	pub loot: Vec<String>, //new ones
+	pub taken: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct SnapshotLog {
+	sequence: u64, //of the last snapshot made
+	acked: Option<(u64, Contents)>,
+	sent: VecDeque<(u64, Contents)>, //after the acknowledged one, oldest first
+}
+
+impl SnapshotLog {
+	//The next snapshot of what the client can see, players with the revision of their
+	//trajectory. Returns what has to be sent of it.
+	pub fn next(&mut self, players: HashMap<String, u64>, loot: HashSet<String>) -> Delta {
+		self.sequence += 1;
+		let contents = Contents { players, loot };
+		if self
+			.acked
+			.as_ref()
+			.is_some_and(|(acked, _)| self.sequence - acked > MAX_BEHIND)
+		{
+			self.resync();
+		}
+		let delta = match &self.acked {
+			None => Delta {
+				sequence: self.sequence,
+				base: None,
+				players: contents.players.keys().cloned().collect(),
+				loot: contents.loot.iter().cloned().collect(),
+				..Delta::default()
+			},
+			Some((base, _)) => {
+				let known: Vec<&Contents> = self.known().collect();
+				Delta {
+					sequence: self.sequence,
+					base: Some(*base),
+					//unchanged only if every snapshot since the base had the same revision
+					players: contents
+						.players
+						.iter()
+						.filter(|(id, revision)| {
+							!known
+								.iter()
+								.all(|sent| sent.players.get(*id) == Some(revision))
+						})
+						.map(|(id, _)| id.clone())
+						.collect(),
+					gone: gone(known.iter().flat_map(|sent| sent.players.keys()), |id| {
+						contents.players.contains_key(id)
+					}),
+					loot: contents
+						.loot
+						.iter()
+						.filter(|loot_id| !known.iter().all(|sent| sent.loot.contains(*loot_id)))
+						.cloned()
+						.collect(),
+					taken: gone(known.iter().flat_map(|sent| &sent.loot), |loot_id| {
+						contents.loot.contains(loot_id)
+					}),
+				}
+			}
+		};
+		self.sent.push_back((self.sequence, contents));
+		if self.sent.len() as u64 > MAX_BEHIND {
+			self.sent.pop_front(); //acknowledging it would be too late anyway
+		}
+		delta
+	}
+
+	//The client applied the snapshot. Acknowledging one that isn't waiting for it, one too old
+	//or one never sent, changes nothing.
+	pub fn ack(&mut self, sequence: u64) -> bool {
+		let Some(index) = self.sent.iter().position(|(sent, _)| *sent == sequence) else {
+			return false;
+		};
+		self.acked = self.sent.drain(..=index).next_back();
+		true
+	}
+
+	//the next snapshot lists everything
+	pub fn resync(&mut self) {
+		self.acked = None;
+	}
+
+	//what the client can have been told since the base
+	fn known(&self) -> impl Iterator<Item = &Contents> {
+		self.acked
+			.iter()
+			.chain(&self.sent)
+			.map(|(_, contents)| contents)
+	}
+}
+
+//the ids among seen that aren't there anymore, once each
+fn gone<'a>(seen: impl Iterator<Item = &'a String>, there: impl Fn(&str) -> bool) -> Vec<String> {
+	let gone: HashSet<&String> = seen.filter(|id| !there(id)).collect();
+	gone.into_iter().cloned().collect()
+}
@@ -0,0 +1,244 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+use wasm_bindgen::convert::{FromWasmAbi, IntoWasmAbi};
+use wasm_bindgen::describe::WasmDescribe;
+
+const FRAC_BITS: u32 = 32;
+const ONE: i64 = 1 << FRAC_BITS;
+
+//Signed 32.32 fixed point number. Every operation is integer arithmetic, so the
+//results are the same bits on every platform. Overflow saturates instead of wrapping.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fixed(i64);
+
+//raw values of the constants, rounded to the nearest representable number
+const PI: Fixed = Fixed(13493037705);
+const TWO_PI: Fixed = Fixed(26986075409);
+const HALF_PI: Fixed = Fixed(6746518852);
+
+//taylor coefficients of sin, (-1)^n / (2n+1)! for n = 1..5
+const SIN_COEFFS: [Fixed; 5] = [
+	Fixed(-715827883),
+	Fixed(35791394),
+	Fixed(-852176),
+	Fixed(11836),
+	Fixed(-108),
+];
+
+impl Fixed {
+	pub const ZERO: Fixed = Fixed(0);
+	pub const MAX: Fixed = Fixed(i64::MAX);
+	pub const MIN: Fixed = Fixed(i64::MIN);
+
+	pub const fn from_raw(raw: i64) -> Fixed {
+		Fixed(raw)
+	}
+
+	pub const fn to_bits(self) -> i64 {
+		self.0
+	}
+
+	//exact for any f32 inside the range, bits past the 32 fractional ones are truncated
+	pub fn from_f64(value: f64) -> Fixed {
+		Fixed((value * ONE as f64) as i64)
+	}
+
+	pub fn to_f64(self) -> f64 {
+		self.0 as f64 / ONE as f64
+	}
+
+	pub fn from_ratio(num: u64, den: u64) -> Fixed {
+		Fixed::saturate((num as i128) * (ONE as i128) / (den as i128))
+	}
+
+	pub fn is_finite(self) -> bool {
+		true
+	}
+
+	pub fn abs(self) -> Fixed {
+		Fixed(self.0.saturating_abs())
+	}
+
+	pub fn min(self, other: Fixed) -> Fixed {
+		Ord::min(self, other)
+	}
+
+	pub fn max(self, other: Fixed) -> Fixed {
+		Ord::max(self, other)
+	}
+
+	pub fn sqrt(self) -> Fixed {
+		if self.0 <= 0 {
+			return Fixed::ZERO;
+		}
+		Fixed::saturate(isqrt((self.0 as u128) << FRAC_BITS) as i128)
+	}
+
+	pub fn sin(self) -> Fixed {
+		//reduce to [-pi, pi] and then to [-pi/2, pi/2] where the series converges quickly
+		let mut x = Fixed(self.0.rem_euclid(TWO_PI.0));
+		if x > PI {
+			x -= TWO_PI;
+		}
+		if x > HALF_PI {
+			x = PI - x;
+		} else if x < -HALF_PI {
+			x = -PI - x;
+		}
+		let x2 = x * x;
+		let mut sum = Fixed::ZERO;
+		for coeff in SIN_COEFFS.iter().rev() {
+			sum = (sum + *coeff) * x2;
+		}
+		x + x * sum
+	}
+
+	pub fn cos(self) -> Fixed {
+		(self + HALF_PI).sin()
+	}
+
+	fn saturate(value: i128) -> Fixed {
+		Fixed(value.clamp(i64::MIN as i128, i64::MAX as i128) as i64)
+	}
+}
+
+//largest integer whose square is at most n, one bit at a time
+fn isqrt(n: u128) -> u128 {
+	let mut remainder = n;
+	let mut result = 0u128;
+	let mut bit = 1u128 << 126;
+	while bit > n {
+		bit >>= 2;
+	}
+	while bit != 0 {
+		if remainder >= result + bit {
+			remainder -= result + bit;
+			result = (result >> 1) + bit;
+		} else {
+			result >>= 1;
+		}
+		bit >>= 2;
+	}
+	result
+}
+
+impl Add for Fixed {
+	type Output = Fixed;
+	fn add(self, other: Fixed) -> Fixed {
+		Fixed(self.0.saturating_add(other.0))
+	}
+}
+
+impl Sub for Fixed {
+	type Output = Fixed;
+	fn sub(self, other: Fixed) -> Fixed {
+		Fixed(self.0.saturating_sub(other.0))
+	}
+}
+
+impl Mul for Fixed {
+	type Output = Fixed;
+	fn mul(self, other: Fixed) -> Fixed {
+		Fixed::saturate(((self.0 as i128) * (other.0 as i128)) >> FRAC_BITS)
+	}
+}
+
+impl Div for Fixed {
+	type Output = Fixed;
+	fn div(self, other: Fixed) -> Fixed {
+		if other.0 == 0 {
+			//same sign as the float division would give, 0/0 is 0
+			return match self.0.signum() {
+				1 => Fixed::MAX,
+				-1 => Fixed::MIN,
+				_ => Fixed::ZERO,
+			};
+		}
+		Fixed::saturate(((self.0 as i128) << FRAC_BITS) / (other.0 as i128))
+	}
+}
+
+impl Neg for Fixed {
+	type Output = Fixed;
+	fn neg(self) -> Fixed {
+		Fixed(self.0.saturating_neg())
+	}
+}
+
+impl AddAssign for Fixed {
+	fn add_assign(&mut self, other: Fixed) {
+		*self = *self + other;
+	}
+}
+
+impl SubAssign for Fixed {
+	fn sub_assign(&mut self, other: Fixed) {
+		*self = *self - other;
+	}
+}
+
+impl MulAssign for Fixed {
+	fn mul_assign(&mut self, other: Fixed) {
+		*self = *self * other;
+	}
+}
+
+impl DivAssign for Fixed {
+	fn div_assign(&mut self, other: Fixed) {
+		*self = *self / other;
+	}
+}
+
+impl fmt::Debug for Fixed {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.to_f64())
+	}
+}
+
+impl fmt::Display for Fixed {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.to_f64())
+	}
+}
+
+//Written as f64 so map files stay readable. The conversion is exact as long as the
+//value needs no more than 53 significant bits, which covers anything below 2^21.
+impl Serialize for Fixed {
+	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+	where
+		S: Serializer,
+	{
+		serializer.serialize_f64(self.to_f64())
+	}
+}
+
+impl<'de> Deserialize<'de> for Fixed {
+	fn deserialize<D>(deserializer: D) -> Result<Fixed, D::Error>
+	where
+		D: Deserializer<'de>,
+	{
+		Ok(Fixed::from_f64(f64::deserialize(deserializer)?))
+	}
+}
+
+//javascript sees a plain number
+impl WasmDescribe for Fixed {
+	fn describe() {
+		f64::describe()
+	}
+}
+
+impl IntoWasmAbi for Fixed {
+	type Abi = <f64 as IntoWasmAbi>::Abi;
+	fn into_abi(self) -> Self::Abi {
+		self.to_f64().into_abi()
+	}
+}
+
+impl FromWasmAbi for Fixed {
+	type Abi = <f64 as FromWasmAbi>::Abi;
+	unsafe fn from_abi(js: Self::Abi) -> Self {
+		Fixed::from_f64(f64::from_abi(js))
+	}
+}
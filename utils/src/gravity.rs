@@ -0,0 +1,193 @@
+use std::fmt;
+use std::sync::OnceLock;
+
+use crate::scalar::{Real, Scalar};
+use crate::trajectory::{Body, Vector};
+
+//Gravity of the static bodies cached on a square grid over the dome. Bodies close to a cell
+//are summed exactly every time, the smooth pull of everything further away is sampled once at
+//the cell's corners and interpolated. Cells are filled the first time something enters them,
+//the values only depend on the map so the result is the same on the server and in wasm.
+
+const CELL_SIZE: f32 = 400.0; //side of a cell on maps of up to MAX_CELLS_PER_SIDE cells across
+const MAX_CELLS_PER_SIDE: usize = 256; //larger maps get larger cells instead
+const NEAR_CELLS: f32 = 4.0; //bodies within this many cells of the cell are summed exactly
+
+pub struct GravityGrid {
+	corner: Vector, //lowest x and y covered by the grid
+	cell_size: Scalar,
+	cells_per_side: usize,
+	cells: Vec<OnceLock<Cell>>,
+	still: Vec<usize>,
+	max_radius: Scalar, //of the still bodies
+	moving: Vec<usize>, //orbiting bodies, their positions change so they are summed exactly
+}
+
+struct Cell {
+	far: [Vector; 4], //pull of the far bodies at the corners, low y first, low x first
+	near: Vec<usize>,
+}
+
+impl GravityGrid {
+	pub fn new(bodies: &[Body], dome_radius: Scalar) -> GravityGrid {
+		let (still, moving): (Vec<usize>, Vec<usize>) =
+			(0..bodies.len()).partition(|&index| bodies[index].orbit.is_none());
+		let max_radius = still
+			.iter()
+			.map(|&index| bodies[index].extent())
+			.fold(Scalar::default(), |a, b| a.max(b));
+		let span = Scalar::from_f32(2.0) * dome_radius;
+		let mut cells_per_side = (span.to_f32() / CELL_SIZE).ceil().max(1.0) as usize;
+		let mut cell_size = Scalar::from_f32(CELL_SIZE);
+		if cells_per_side > MAX_CELLS_PER_SIDE {
+			cells_per_side = MAX_CELLS_PER_SIDE;
+			cell_size = span / Scalar::from_f32(MAX_CELLS_PER_SIDE as f32);
+		}
+		GravityGrid {
+			corner: Vector {
+				x: -dome_radius,
+				y: -dome_radius,
+			},
+			cell_size,
+			cells_per_side,
+			cells: (0..cells_per_side * cells_per_side)
+				.map(|_| OnceLock::new())
+				.collect(),
+			still,
+			max_radius,
+			moving,
+		}
+	}
+
+	//acceleration at pos, bodies are the map's bodies at the time of interest
+	//(only the orbiting ones can have moved since the grid was made)
+	pub fn acceleration(&self, bodies: &[Body], pos: &Vector) -> Vector {
+		let mut pull = match self.cell_at(pos) {
+			Some((column, row)) => self.cell_pull(bodies, column, row, pos),
+			//only gen_spawn looks outside the dome, it can afford the full sum
+			None => add_pulls(bodies, &self.still, pos),
+		};
+		let moving = add_pulls(bodies, &self.moving, pos);
+		pull.x += moving.x;
+		pull.y += moving.y;
+		pull
+	}
+
+	//The still bodies that a circle of the given radius around pos could touch, or None when
+	//the circle reaches past the bodies its cell keeps as near. Orbiting bodies are left out.
+	pub fn near_bodies(&self, bodies: &[Body], pos: &Vector, radius: Scalar) -> Option<&[usize]> {
+		if radius + self.max_radius > self.cell_size * Scalar::from_f32(NEAR_CELLS) {
+			return None;
+		}
+		let (column, row) = self.cell_at(pos)?;
+		Some(&self.cell(bodies, column, row).near)
+	}
+
+	pub fn moving(&self) -> &[usize] {
+		&self.moving
+	}
+
+	fn cell(&self, bodies: &[Body], column: usize, row: usize) -> &Cell {
+		self.cells[row * self.cells_per_side + column]
+			.get_or_init(|| self.fill_cell(bodies, column, row))
+	}
+
+	fn cell_at(&self, pos: &Vector) -> Option<(usize, usize)> {
+		let column = ((pos.x - self.corner.x) / self.cell_size).to_f32().floor();
+		let row = ((pos.y - self.corner.y) / self.cell_size).to_f32().floor();
+		let range = 0.0..self.cells_per_side as f32;
+		if range.contains(&column) && range.contains(&row) {
+			Some((column as usize, row as usize))
+		} else {
+			None
+		}
+	}
+
+	fn cell_pull(&self, bodies: &[Body], column: usize, row: usize, pos: &Vector) -> Vector {
+		let cell = self.cell(bodies, column, row);
+		let origin = self.cell_origin(column, row);
+		let tx = (pos.x - origin.x) / self.cell_size;
+		let ty = (pos.y - origin.y) / self.cell_size;
+		let lerp = |a: Vector, b: Vector, t: Scalar| Vector {
+			x: a.x + (b.x - a.x) * t,
+			y: a.y + (b.y - a.y) * t,
+		};
+		let [low_left, low_right, high_left, high_right] = cell.far;
+		let far = lerp(
+			lerp(low_left, low_right, tx),
+			lerp(high_left, high_right, tx),
+			ty,
+		);
+		let near = add_pulls(bodies, &cell.near, pos);
+		Vector {
+			x: far.x + near.x,
+			y: far.y + near.y,
+		}
+	}
+
+	fn fill_cell(&self, bodies: &[Body], column: usize, row: usize) -> Cell {
+		let low = self.cell_origin(column, row);
+		let high = Vector {
+			x: low.x + self.cell_size,
+			y: low.y + self.cell_size,
+		};
+		let reach = self.cell_size * Scalar::from_f32(NEAR_CELLS);
+		let (near, far): (Vec<usize>, Vec<usize>) =
+			self.still.iter().copied().partition(|&index| {
+				let pos = bodies[index].pos;
+				low.x - reach <= pos.x
+					&& pos.x < high.x + reach
+					&& low.y - reach <= pos.y
+					&& pos.y < high.y + reach
+			});
+		let corners = [
+			(low.x, low.y),
+			(high.x, low.y),
+			(low.x, high.y),
+			(high.x, high.y),
+		];
+		Cell {
+			far: corners.map(|(x, y)| add_pulls(bodies, &far, &Vector { x, y })),
+			near,
+		}
+	}
+
+	fn cell_origin(&self, column: usize, row: usize) -> Vector {
+		Vector {
+			x: self.corner.x + self.cell_size * Scalar::from_f32(column as f32),
+			y: self.corner.y + self.cell_size * Scalar::from_f32(row as f32),
+		}
+	}
+}
+
+//a cloned map starts over with empty cells, they are cheap to fill again
+impl Clone for GravityGrid {
+	fn clone(&self) -> GravityGrid {
+		GravityGrid {
+			cells: (0..self.cells.len()).map(|_| OnceLock::new()).collect(),
+			still: self.still.clone(),
+			max_radius: self.max_radius,
+			moving: self.moving.clone(),
+			..*self
+		}
+	}
+}
+
+impl fmt::Debug for GravityGrid {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("GravityGrid")
+			.field("cell_size", &self.cell_size)
+			.field("cells_per_side", &self.cells_per_side)
+			.finish_non_exhaustive()
+	}
+}
+
+fn add_pulls(bodies: &[Body], indices: &[usize], pos: &Vector) -> Vector {
+	let mut pull = Vector::default();
+	for &index in indices {
+		let body_pull = bodies[index].pull(pos);
+		pull.x += body_pull.x;
+		pull.y += body_pull.y;
+	}
+	pull
+}
@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+
+use crate::scalar::{Real, Scalar};
+use crate::trajectory::{Trajectory, Vector, PLAYER_RADIUS};
+
+//A thrown grenade flies like a ship with its engine off, so it falls with the same gravity and
+//integrator as the players and a client can draw its arc by stepping the trajectory the server
+//sends. It is as big as a ship when touching bodies.
+
+pub const MAX_PRESS_TIME: u64 = 1500; //holding the trigger longer doesn't throw any harder
+const MIN_THROW_SPEED: f32 = 100.0; //relative to the thrower
+const MAX_THROW_SPEED: f32 = 500.0;
+
+//how grenades go off, the server's settings can change it
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Blast {
+	pub fuse: u64,   //millis from the throw until it goes off by itself
+	pub radius: f32, //from the centre to the closest point of a hull
+	pub damage: f32, //at the centre, falling off linearly to nothing at the edge
+}
+
+impl Default for Blast {
+	fn default() -> Blast {
+		Blast {
+			fuse: 3000,
+			radius: 250.0,
+			damage: 80.0,
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct Grenade {
+	pub thrower: String,
+	pub flight: Trajectory,
+	pub fuse_end: u64,
+	pub blast: Blast,
+}
+
+impl Grenade {
+	//thrown the way the ship faces, harder the longer the trigger was held
+	pub fn throw(thrower: String, from: &Trajectory, press_time: u64, blast: &Blast) -> Grenade {
+		let held = press_time.min(MAX_PRESS_TIME) as f32 / MAX_PRESS_TIME as f32;
+		let speed = MIN_THROW_SPEED + (MAX_THROW_SPEED - MIN_THROW_SPEED) * held;
+		Grenade {
+			thrower,
+			flight: from.launched(Scalar::from_f32(speed)),
+			fuse_end: from.time + blast.fuse,
+			blast: *blast,
+		}
+	}
+
+	//Steps the flight up to time. Returns where the grenade went off if the fuse ended or it
+	//touched a body on the way, it doesn't move after that.
+	pub fn advance(&mut self, time: u64) -> Option<Vector> {
+		self.flight.advance(time.min(self.fuse_end));
+		if self.flight.health == 0 || self.flight.time >= self.fuse_end {
+			Some(self.flight.pos)
+		} else {
+			None
+		}
+	}
+
+	//health lost by a ship at target when the grenade goes off
+	pub fn damage_to(&self, target: &Trajectory) -> u8 {
+		let dx = target.pos.x.to_f32() - self.flight.pos.x.to_f32();
+		let dy = target.pos.y.to_f32() - self.flight.pos.y.to_f32();
+		let gap = ((dx * dx + dy * dy).sqrt() - PLAYER_RADIUS).max(0.0);
+		if gap >= self.blast.radius {
+			return 0;
+		}
+		(self.blast.damage * (1.0 - gap / self.blast.radius)).ceil() as u8
+	}
+}
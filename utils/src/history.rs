@@ -0,0 +1,231 @@
+use std::collections::VecDeque;
+
+use crate::trajectory::{Trajectory, UpdateTypeWrapper, TIMESTEP_MILLIS};
+
+const WINDOW: u64 = 10000; //millis of history kept behind the latest snapshot
+const CAPACITY: usize = 128; //snapshots kept at most, the window gets shorter if there are more
+const CHECKPOINT_INTERVAL: u64 = 1000; //so rebuilding a state never needs more steps than this
+
+//a player's trajectory right after something other than step changed it
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+	pub trajectory: Trajectory,
+	pub change: Option<UpdateTypeWrapper>, //None for spawns, corrections and checkpoints
+	pub checkpoint: bool,                  //only stepped to, so it can be stepped to again
+}
+
+//Past states of one player. Between two snapshots the trajectory only steps, so the state at
+//any time after the oldest snapshot is the latest snapshot before it, stepped up to it.
+#[derive(Debug, Clone, Default)]
+pub struct TrajectoryHistory {
+	snapshots: VecDeque<Snapshot>,
+	revision: u64, //how many changes were recorded, checkpoints aren't changes
+}
+
+impl TrajectoryHistory {
+	pub fn new(start: &Trajectory) -> TrajectoryHistory {
+		let mut history = TrajectoryHistory::default();
+		history.record(start, None);
+		history
+	}
+
+	pub fn record(&mut self, trajectory: &Trajectory, change: Option<UpdateTypeWrapper>) {
+		self.revision += 1;
+		self.push(trajectory, change, false);
+	}
+
+	//records the trajectory if it has stepped far past the last snapshot
+	pub fn checkpoint(&mut self, trajectory: &Trajectory) {
+		let due = self
+			.snapshots
+			.back()
+			.is_none_or(|last| trajectory.time >= last.trajectory.time + CHECKPOINT_INTERVAL);
+		if due {
+			self.push(trajectory, None, true);
+		}
+	}
+
+	//Changes whenever something other than step changed the trajectory. While it stays the same,
+	//stepping the trajectory from when it was last seen gets to where it is now.
+	pub fn revision(&self) -> u64 {
+		self.revision
+	}
+
+	fn push(
+		&mut self,
+		trajectory: &Trajectory,
+		change: Option<UpdateTypeWrapper>,
+		checkpoint: bool,
+	) {
+		//a respawn can start before the last snapshots of the previous life, those never happened
+		while self
+			.snapshots
+			.back()
+			.is_some_and(|last| last.trajectory.time > trajectory.time)
+		{
+			self.snapshots.pop_back();
+		}
+		self.snapshots.push_back(Snapshot {
+			trajectory: trajectory.clone(),
+			change,
+			checkpoint,
+		});
+		//the oldest snapshot is kept as long as it is needed to rebuild the start of the window
+		let horizon = trajectory.time.saturating_sub(WINDOW);
+		while self.snapshots.len() > CAPACITY
+			|| (self.snapshots.len() > 1 && self.snapshots[1].trajectory.time <= horizon)
+		{
+			self.snapshots.pop_front();
+		}
+	}
+
+	//the state after the last step at or before time, None if that is before the history starts
+	pub fn at(&self, time: u64) -> Option<Trajectory> {
+		let after = self
+			.snapshots
+			.partition_point(|snapshot| snapshot.trajectory.time <= time);
+		let mut trajectory = self
+			.snapshots
+			.get(after.checked_sub(1)?)?
+			.trajectory
+			.clone();
+		trajectory.advance_to_time(time);
+		Some(trajectory)
+	}
+
+	//Steps a trajectory taken from the history the way the player went, with the changes
+	//recorded on the way made too
+	pub fn follow(&self, trajectory: &mut Trajectory) {
+		let next = trajectory.time + TIMESTEP_MILLIS as u64;
+		let after = self
+			.snapshots
+			.partition_point(|snapshot| snapshot.trajectory.time <= trajectory.time);
+		let recorded = self
+			.snapshots
+			.get(after)
+			.is_some_and(|snapshot| snapshot.trajectory.time <= next);
+		match self.at(next) {
+			Some(followed) if recorded => *trajectory = followed,
+			_ => {
+				trajectory.step();
+			}
+		}
+	}
+
+	//Replaces what happened from the trajectory's time on with it, for when something changed
+	//the past. The changes and spawns recorded after it are made again on top, and the state
+	//they lead to at until is returned.
+	pub fn rewrite(&mut self, trajectory: &Trajectory, until: u64) -> Trajectory {
+		let later: Vec<Snapshot> = self
+			.snapshots
+			.iter()
+			.filter(|snapshot| snapshot.trajectory.time > trajectory.time)
+			.cloned()
+			.collect();
+		self.record(trajectory, None);
+		let mut current = trajectory.clone();
+		for snapshot in later {
+			match snapshot.change {
+				Some(change) => {
+					current.advance_to_time(snapshot.trajectory.time);
+					current.apply_change(change.clone());
+					self.record(&current, Some(change));
+				}
+				None if snapshot.checkpoint => {}
+				None => {
+					current = snapshot.trajectory;
+					self.record(&current, None);
+				}
+			}
+		}
+		current.advance_to_time(until);
+		current
+	}
+
+	//the earliest time at can answer
+	pub fn start(&self) -> Option<u64> {
+		self.snapshots
+			.front()
+			.map(|snapshot| snapshot.trajectory.time)
+	}
+
+	pub fn snapshots(&self) -> impl Iterator<Item = &Snapshot> {
+		self.snapshots.iter()
+	}
+}
+
+//two ships that bounced off each other, indices are into the slice given to advance_together
+#[derive(Debug, Clone)]
+pub struct ShipContact {
+	pub first: usize,
+	pub second: usize,
+	pub damage: u8,
+	pub after: [Trajectory; 2], //both ships right after the bounce
+}
+
+//Looks for ships touching from from up to time and steps the ones behind up to it. Ships are
+//taken back to where they were at from and follow their history, always the one furthest
+//behind so that all others are less than a step ahead of it. Ships that got ahead by an update
+//are bounced where they touched and their history is made again from there. Ships that are
+//dead by now aren't brought back by a bounce and are left out.
+pub fn advance_together(
+	ships: &mut [(&mut Trajectory, &mut TrajectoryHistory)],
+	from: u64,
+	time: u64,
+) -> Vec<ShipContact> {
+	let step = TIMESTEP_MILLIS as u64;
+	let mut paths: Vec<Option<Trajectory>> = ships
+		.iter()
+		.map(|(trajectory, history)| match trajectory.health {
+			0 => None,
+			_ if trajectory.time <= from => Some((**trajectory).clone()),
+			//or from where they spawned if that was later
+			_ => history.at(from.max(history.start()?)),
+		})
+		.collect();
+	let mut contacts = Vec::new();
+	loop {
+		let behind = paths
+			.iter()
+			.enumerate()
+			.filter_map(|(index, path)| Some((index, path.as_ref()?)))
+			.filter(|(_, path)| path.health > 0 && path.time + step <= time)
+			.min_by_key(|(_, path)| path.time)
+			.map(|(index, _)| index);
+		let Some(index) = behind else {
+			break;
+		};
+		if let Some(path) = paths[index].as_mut() {
+			ships[index].1.follow(path);
+		}
+		for other in (0..paths.len()).filter(|&other| other != index) {
+			let (first, second) = (index.min(other), index.max(other));
+			let (low, high) = paths.split_at_mut(second);
+			let (Some(a), Some(b)) = (low[first].as_mut(), high[0].as_mut()) else {
+				continue;
+			};
+			if a.time.abs_diff(b.time) >= step {
+				continue;
+			}
+			if let Some(damage) = a.collide_with(b) {
+				contacts.push(ShipContact {
+					first,
+					second,
+					damage,
+					after: [a.clone(), b.clone()],
+				});
+				for (ship, path) in [(first, &*a), (second, &*b)] {
+					let (trajectory, history) = &mut ships[ship];
+					**trajectory = history.rewrite(path, trajectory.time);
+				}
+			}
+		}
+	}
+	//the ones that were behind are where their paths got to
+	for ((trajectory, _), path) in ships.iter_mut().zip(paths) {
+		if let Some(path) = path.filter(|path| path.time > trajectory.time) {
+			**trajectory = path;
+		}
+	}
+	contacts
+}
@@ -0,0 +1,163 @@
+use std::collections::{HashMap, HashSet};
+
+use kdbush::KDBush;
+
+//Who is told about what. Every tick the server indexes where the players are and works out what
+//each of them can see, clients only get the players, loot and events within their view. So
+//that something on the edge doesn't keep entering and leaving, it is only dropped from a view
+//once it is a bit further away than where it was let in.
+
+pub const VIEW_RADIUS: f32 = 4000.0;
+const LEAVE_RADIUS: f32 = VIEW_RADIUS * 1.1;
+
+//where a ship or a piece of loot is
+#[derive(Debug, Clone)]
+pub struct Spot {
+	pub id: String,
+	pub x: f32,
+	pub y: f32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct View {
+	pub players: HashSet<String>, //living ones, never the viewer itself
+	pub loot: HashSet<String>,
+}
+
+impl View {
+	pub fn is_empty(&self) -> bool {
+		self.players.is_empty() && self.loot.is_empty()
+	}
+}
+
+//what a refresh changed about a view
+#[derive(Debug, Clone, Default)]
+pub struct ViewChange {
+	pub entered: View,
+	pub left: View,
+}
+
+#[derive(Default)]
+pub struct Interest {
+	viewers: Vec<Spot>,    //every player, dead ones still look around where they died
+	index: Option<KDBush>, //over the viewers, None while there are none
+	views: HashMap<String, View>,
+	watchers: HashMap<String, HashSet<String>>, //who sees each player and piece of loot
+}
+
+impl Interest {
+	//Works out every view again from where the ships are, the ones with alive set can be seen.
+	//Returns what came into and went out of each view that changed.
+	pub fn refresh(
+		&mut self,
+		ships: Vec<(Spot, bool)>,
+		loot: Vec<Spot>,
+	) -> HashMap<String, ViewChange> {
+		let alive: Vec<bool> = ships.iter().map(|(_, alive)| *alive).collect();
+		self.viewers = ships.into_iter().map(|(spot, _)| spot).collect();
+		self.index = index(&self.viewers);
+		let loot_index = index(&loot);
+		let mut views = HashMap::new();
+		let mut changes = HashMap::new();
+		for (number, viewer) in self.viewers.iter().enumerate() {
+			let before = self.views.remove(&viewer.id).unwrap_or_default();
+			let mut view = View::default();
+			around(&self.index, &self.viewers, viewer, |index, distance| {
+				if index != number
+					&& alive[index] && in_sight(&before.players, &self.viewers[index].id, distance)
+				{
+					view.players.insert(self.viewers[index].id.clone());
+				}
+			});
+			around(&loot_index, &loot, viewer, |index, distance| {
+				if in_sight(&before.loot, &loot[index].id, distance) {
+					view.loot.insert(loot[index].id.clone());
+				}
+			});
+			let change = ViewChange {
+				entered: difference(&view, &before),
+				left: difference(&before, &view),
+			};
+			if !change.entered.is_empty() || !change.left.is_empty() {
+				changes.insert(viewer.id.clone(), change);
+			}
+			views.insert(viewer.id.clone(), view);
+		}
+		self.views = views;
+		self.watchers.clear();
+		for (viewer, view) in &self.views {
+			for seen in view.players.iter().chain(&view.loot) {
+				self.watchers
+					.entry(seen.clone())
+					.or_default()
+					.insert(viewer.clone());
+			}
+		}
+		changes
+	}
+
+	//what the viewer saw at the last refresh
+	pub fn view(&self, viewer: &str) -> Option<&View> {
+		self.views.get(viewer)
+	}
+
+	//The players something is of interest to: the ones seeing any of the subjects or seeing
+	//where it happened, and the subjects themselves.
+	pub fn audience(&self, subjects: &[&str], place: Option<(f32, f32)>) -> HashSet<String> {
+		let mut audience: HashSet<String> = subjects.iter().map(|id| id.to_string()).collect();
+		for subject in subjects {
+			if let Some(watchers) = self.watchers.get(*subject) {
+				audience.extend(watchers.iter().cloned());
+			}
+		}
+		if let (Some((x, y)), Some(index)) = (place, &self.index) {
+			index.within(x as f64, y as f64, VIEW_RADIUS as f64, |number| {
+				audience.insert(self.viewers[number].id.clone());
+			});
+		}
+		audience
+	}
+}
+
+fn index(spots: &[Spot]) -> Option<KDBush> {
+	if spots.is_empty() {
+		return None;
+	}
+	let points: Vec<(f64, f64)> = spots
+		.iter()
+		.map(|spot| (spot.x as f64, spot.y as f64))
+		.collect();
+	Some(KDBush::create(points, kdbush::DEFAULT_NODE_SIZE))
+}
+
+//visits what is within the leave radius of the viewer, with its distance
+fn around(
+	index: &Option<KDBush>,
+	spots: &[Spot],
+	viewer: &Spot,
+	mut visit: impl FnMut(usize, f32),
+) {
+	if let Some(index) = index {
+		index.within(
+			viewer.x as f64,
+			viewer.y as f64,
+			LEAVE_RADIUS as f64,
+			|number| {
+				let (dx, dy) = (spots[number].x - viewer.x, spots[number].y - viewer.y);
+				visit(number, (dx * dx + dy * dy).sqrt());
+			},
+		);
+	}
+}
+
+//new things need to come within the view radius, ones already seen only leave past the leave radius
+fn in_sight(seen: &HashSet<String>, id: &str, distance: f32) -> bool {
+	distance <= VIEW_RADIUS || seen.contains(id)
+}
+
+fn difference(view: &View, other: &View) -> View {
+	View {
+		players: view.players.difference(&other.players).cloned().collect(),
+		loot: view.loot.difference(&other.loot).cloned().collect(),
+	}
+}
@@ -1,8 +1,29 @@
+pub mod fixed;
+pub mod gravity;
+pub mod map;
+pub mod messages;
+pub mod protocol;
+pub mod scalar;
 pub mod shared_gameobjects;
+pub mod timeline;
 pub mod trajectory;
 
 #[cfg(target_arch = "wasm32")]
 pub mod background;
 
+#[cfg(not(target_arch = "wasm32"))]
+pub mod bullet;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod conduct;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod delta;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod grenade;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod history;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod interest;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod limits;
 #[cfg(not(target_arch = "wasm32"))]
 pub mod server_gameobjects;
@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Deserializer};
+
+use crate::messages::ClientMessage;
+
+//How much a client may send. Each kind of message has a token bucket, refilled at a steady
+//rate up to a burst, and every frame also takes from a bucket shared by all of them. Messages
+//over a limit are dropped. Going over a limit now and then is forgiven, but each time takes
+//from a tolerance bucket of its own and a client that empties it is disconnected.
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rate {
+	pub per_second: f32,
+	pub burst: f32,
+}
+
+impl Rate {
+	pub const fn new(per_second: f32, burst: f32) -> Rate {
+		Rate { per_second, burst }
+	}
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limits {
+	//by message name, ones not listed only count towards total. Read from a config, the listed
+	//ones replace the defaults and the others keep theirs.
+	#[serde(deserialize_with = "over_default_rates")]
+	pub rates: HashMap<String, Rate>,
+	pub total: Rate,
+	pub tolerance: Rate, //times over a limit, a client going over more often is disconnected
+	pub max_frame: usize, //bytes, a client sending a larger frame is disconnected
+}
+
+impl Default for Limits {
+	fn default() -> Limits {
+		let rates = [
+			("Ping", Rate::new(1.0, 3.0)),
+			("TrajectoryUpdate", Rate::new(20.0, 40.0)),
+			("ChangeSlot", Rate::new(10.0, 20.0)),
+			("ClaimLoot", Rate::new(10.0, 20.0)),
+			("Correct", Rate::new(5.0, 20.0)), //one for each ship in view until it is answered
+			("TriggerPress", Rate::new(10.0, 20.0)),
+			("Shoot", Rate::new(10.0, 20.0)),
+			("StateQuery", Rate::new(0.2, 2.0)),
+			("Spawn", Rate::new(1.0, 3.0)),
+		];
+		Limits {
+			rates: rates
+				.into_iter()
+				.map(|(name, rate)| (name.to_string(), rate))
+				.collect(),
+			total: Rate::new(200.0, 400.0),
+			tolerance: Rate::new(1.0, 20.0),
+			max_frame: 4096,
+		}
+	}
+}
+
+impl Limits {
+	//with snapshot acks let through at the tick rate, each snapshot is acknowledged once and they
+	//can come in bunches after a stall. They also get room of their own in total.
+	pub fn paced(&self, tick_rate: u64) -> Limits {
+		let ticks = tick_rate as f32;
+		let mut limits = self.clone();
+		limits
+			.rates
+			.insert("AckSnapshot".to_string(), Rate::new(ticks, ticks * 2.0));
+		limits.total.per_second += ticks;
+		limits.total.burst += ticks * 2.0;
+		limits
+	}
+
+	pub fn validate(&self) -> Result<(), String> {
+		let rates = self.rates.iter().map(|(name, rate)| (name.as_str(), rate));
+		let all = [("total", &self.total), ("tolerance", &self.tolerance)];
+		if let Some(name) = self
+			.rates
+			.keys()
+			.find(|name| !ClientMessage::NAMES.contains(&name.as_str()))
+		{
+			return Err(format!("there are no messages named {}", name));
+		}
+		for (name, rate) in rates.chain(all) {
+			if !(0.0..).contains(&rate.per_second) {
+				return Err(format!("the rate of {} can't be negative", name));
+			}
+			if !(1.0..).contains(&rate.burst) {
+				return Err(format!(
+					"the burst of {} has to allow at least one message",
+					name
+				));
+			}
+		}
+		if self.max_frame == 0 {
+			return Err("max_frame has to be at least a byte".to_string());
+		}
+		Ok(())
+	}
+}
+
+fn over_default_rates<'de, D: Deserializer<'de>>(
+	deserializer: D,
+) -> Result<HashMap<String, Rate>, D::Error> {
+	let mut rates = Limits::default().rates;
+	rates.extend(HashMap::<String, Rate>::deserialize(deserializer)?);
+	Ok(rates)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Verdict {
+	Allowed,
+	Limited,    //drop the message
+	Disconnect, //the client keeps going over its limits
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+	tokens: f32,
+	time: u64, //when tokens was last worked out
+}
+
+impl Bucket {
+	fn full(rate: &Rate, time: u64) -> Bucket {
+		Bucket {
+			tokens: rate.burst,
+			time,
+		}
+	}
+
+	fn take(&mut self, rate: &Rate, time: u64) -> bool {
+		let elapsed = time.saturating_sub(self.time) as f32 / 1000.0;
+		self.tokens = (self.tokens + elapsed * rate.per_second).min(rate.burst);
+		self.time = self.time.max(time);
+		if self.tokens < 1.0 {
+			return false;
+		}
+		self.tokens -= 1.0;
+		true
+	}
+}
+
+//the buckets of one client
+#[derive(Debug)]
+pub struct Limiter {
+	limits: Limits,
+	buckets: HashMap<String, Bucket>,
+	total: Bucket,
+	tolerance: Bucket,
+	hits: HashMap<String, u32>, //times over each limit, total is counted as "total"
+}
+
+impl Limiter {
+	pub fn new(limits: &Limits, time: u64) -> Limiter {
+		Limiter {
+			limits: limits.clone(),
+			buckets: HashMap::new(),
+			total: Bucket::full(&limits.total, time),
+			tolerance: Bucket::full(&limits.tolerance, time),
+			hits: HashMap::new(),
+		}
+	}
+
+	//whether a message of the given name can be let through at time, in millis
+	pub fn allow(&mut self, name: &str, time: u64) -> Verdict {
+		let limited = if !self.total.take(&self.limits.total, time) {
+			"total"
+		} else {
+			match self.limits.rates.get(name) {
+				Some(rate) => {
+					let bucket = self
+						.buckets
+						.entry(name.to_string())
+						.or_insert_with(|| Bucket::full(rate, time));
+					if bucket.take(rate, time) {
+						return Verdict::Allowed;
+					}
+					name
+				}
+				None => return Verdict::Allowed,
+			}
+		};
+		*self.hits.entry(limited.to_string()).or_default() += 1;
+		if self.tolerance.take(&self.limits.tolerance, time) {
+			Verdict::Limited
+		} else {
+			Verdict::Disconnect
+		}
+	}
+
+	//how many times each limit was gone over
+	pub fn hits(&self) -> &HashMap<String, u32> {
+		&self.hits
+	}
+}
@@ -0,0 +1,259 @@
+use bincode::serialize;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::f32::consts::PI;
+use std::sync::{Arc, OnceLock, RwLock};
+use xxhash_rust::xxh3::xxh3_64;
+
+use crate::gravity::GravityGrid;
+use crate::scalar::{Real, Scalar};
+use crate::trajectory::{Body, Integrator, Vector};
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+
+//the map every trajectory is simulated against, set once by the server at startup
+//and by the client after fetching it from the server
+static MAP: RwLock<Option<Arc<Map>>> = RwLock::new(None);
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Map {
+	pub dome_radius: Scalar, //the largest the dome gets
+	pub bodies: Vec<Body>,
+	#[serde(default)]
+	pub integrator: Integrator,
+	#[serde(default)]
+	pub dome_schedule: Option<DomeSchedule>, //the dome keeps its radius without one
+
+	#[serde(skip)]
+	gravity: OnceLock<GravityGrid>, //made on first use
+}
+
+//An elliptical path around the parent body, or around the body's own pos if it has no parent.
+//The angle advances at a constant rate, so the position only depends on the time.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct Orbit {
+	#[serde(default)]
+	pub parent: Option<usize>, //index of a body that comes earlier in the map
+	pub semi_major: Scalar,
+	#[serde(default)]
+	pub eccentricity: Scalar, //0 is a circle
+	#[serde(default)]
+	pub rotation: Scalar, //direction of the major axis in radians
+	pub period: u64, //milliseconds per revolution
+	#[serde(default)]
+	pub phase: Scalar, //angle in radians at time 0
+	#[serde(default)]
+	pub retrograde: bool,
+}
+
+//How the dome radius changes during a round. Rounds repeat every period, the radius moves
+//linearly from one stage to the next and stays at the last stage's until the round ends.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DomeSchedule {
+	pub period: u64,            //milliseconds per round
+	pub stages: Vec<DomeStage>, //the first starts the round, at 0
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct DomeStage {
+	pub at: u64, //milliseconds into the round
+	pub radius: Scalar,
+}
+
+impl DomeSchedule {
+	fn radius_at(&self, time: u64) -> Scalar {
+		let into = time % self.period;
+		let next = self.stages.partition_point(|stage| stage.at <= into);
+		let from = &self.stages[next - 1];
+		match self.stages.get(next) {
+			Some(to) => {
+				let progress = Scalar::from_ratio(into - from.at, to.at - from.at);
+				from.radius + (to.radius - from.radius) * progress
+			}
+			None => from.radius,
+		}
+	}
+}
+
+impl Orbit {
+	//offset from the centre of the orbit, the centre sits on a focus of the ellipse
+	fn offset(&self, time: u64) -> Vector {
+		let turn = Scalar::from_ratio(time % self.period, self.period);
+		let direction = Scalar::from_f32(if self.retrograde { -2.0 * PI } else { 2.0 * PI });
+		let angle = self.phase + direction * turn;
+		let semi_minor = self.semi_major
+			* (Scalar::from_f32(1.0) - self.eccentricity * self.eccentricity).sqrt();
+		let x = self.semi_major * (angle.cos_det() - self.eccentricity);
+		let y = semi_minor * angle.sin_det();
+		let (rcos, rsin) = (self.rotation.cos_det(), self.rotation.sin_det());
+		Vector {
+			x: x * rcos - y * rsin,
+			y: x * rsin + y * rcos,
+		}
+	}
+}
+
+impl Map {
+	pub fn from_json(data: &str) -> Result<Map, String> {
+		let map: Map = serde_json::from_str(data).map_err(|e| format!("Invalid map: {}", e))?;
+		map.validate()?;
+		Ok(map)
+	}
+
+	#[cfg(not(target_arch = "wasm32"))]
+	pub fn from_toml(data: &str) -> Result<Map, String> {
+		let map: Map = toml::from_str(data).map_err(|e| format!("Invalid map: {}", e))?;
+		map.validate()?;
+		Ok(map)
+	}
+
+	//format is picked from the extension, anything other than .json is parsed as toml
+	#[cfg(not(target_arch = "wasm32"))]
+	pub fn from_file(path: &Path) -> Result<Map, String> {
+		let data = fs::read_to_string(path)
+			.map_err(|e| format!("Can't read map {}: {}", path.display(), e))?;
+		match path.extension().and_then(|ext| ext.to_str()) {
+			Some("json") => Map::from_json(&data),
+			_ => Map::from_toml(&data),
+		}
+	}
+
+	//bodies with their positions at the given time, static maps are borrowed as they are
+	pub fn bodies_at(&self, time: u64) -> Cow<'_, [Body]> {
+		if self.gravity().moving().is_empty() {
+			return Cow::Borrowed(&self.bodies);
+		}
+		let mut bodies = self.bodies.clone();
+		for index in 0..bodies.len() {
+			if let Some(orbit) = bodies[index].orbit {
+				let centre = match orbit.parent {
+					Some(parent) => bodies[parent].pos,
+					None => bodies[index].pos,
+				};
+				let offset = orbit.offset(time);
+				bodies[index].pos = Vector {
+					x: centre.x + offset.x,
+					y: centre.y + offset.y,
+				};
+			}
+		}
+		Cow::Owned(bodies)
+	}
+
+	//the dome radius at the given time, a pure function of it so every trajectory agrees
+	pub fn dome_radius_at(&self, time: u64) -> Scalar {
+		match &self.dome_schedule {
+			Some(schedule) => schedule.radius_at(time),
+			None => self.dome_radius,
+		}
+	}
+
+	pub fn gravity(&self) -> &GravityGrid {
+		self.gravity
+			.get_or_init(|| GravityGrid::new(&self.bodies, self.dome_radius))
+	}
+
+	pub fn to_json(&self) -> String {
+		serde_json::to_string(self).expect("Failed to serialize map")
+	}
+
+	//stable across platforms since bincode writes floats as their little endian bits
+	pub fn hash_str(&self) -> String {
+		format!("{:x}", xxh3_64(&serialize(self).unwrap()))
+	}
+
+	fn validate(&self) -> Result<(), String> {
+		let zero = Scalar::default();
+		if !(self.dome_radius.is_finite() && self.dome_radius > zero) {
+			return Err(format!("Invalid dome radius {}", self.dome_radius));
+		}
+		if let Some(schedule) = &self.dome_schedule {
+			if schedule.stages.first().map(|stage| stage.at) != Some(0) {
+				return Err("The dome schedule must have a stage at 0".to_string());
+			}
+			for (index, stage) in schedule.stages.iter().enumerate() {
+				if !(stage.radius.is_finite()
+					&& zero < stage.radius
+					&& stage.radius <= self.dome_radius)
+				{
+					return Err(format!(
+						"Dome stage {} has invalid radius {}",
+						index, stage.radius
+					));
+				}
+				if stage.at >= schedule.period {
+					return Err(format!("Dome stage {} starts after the round ends", index));
+				}
+				if index > 0 && stage.at <= schedule.stages[index - 1].at {
+					return Err(format!(
+						"Dome stage {} must start after the one before",
+						index
+					));
+				}
+			}
+		}
+		for (index, body) in self.bodies.iter().enumerate() {
+			if !(body.radius.is_finite() && body.radius > zero) {
+				return Err(format!("Body {} has invalid radius {}", index, body.radius));
+			}
+			if !(body.pos.x.is_finite() && body.pos.y.is_finite()) {
+				return Err(format!("Body {} has invalid position", index));
+			}
+			if !(body.extent().is_finite() && body.extent() > zero) {
+				return Err(format!("Body {} has an invalid outline", index));
+			}
+			if let Some(orbit) = &body.orbit {
+				//parents come first so positions can be resolved in order without cycles
+				if orbit.parent.is_some_and(|parent| parent >= index) {
+					return Err(format!("Body {} must come after its parent", index));
+				}
+				if orbit.period == 0 {
+					return Err(format!("Body {} has an orbit with no period", index));
+				}
+				if !(orbit.semi_major.is_finite() && orbit.semi_major > zero) {
+					return Err(format!("Body {} has invalid semi major axis", index));
+				}
+				if !(zero..Scalar::from_f32(1.0)).contains(&orbit.eccentricity) {
+					return Err(format!("Body {} has eccentricity outside [0, 1)", index));
+				}
+				if !(orbit.rotation.is_finite() && orbit.phase.is_finite()) {
+					return Err(format!("Body {} has invalid orbit angles", index));
+				}
+			}
+		}
+		Ok(())
+	}
+}
+
+pub fn set_map(map: Map) {
+	*MAP.write().unwrap() = Some(Arc::new(map));
+}
+
+pub fn current_map() -> Arc<Map> {
+	MAP.read()
+		.unwrap()
+		.clone()
+		.expect("No map loaded, call set_map first")
+}
+
+//returns the hash of the loaded map, or None if it could not be parsed
+#[wasm_bindgen]
+#[cfg(target_arch = "wasm32")]
+pub fn load_map(data: String) -> Option<String> {
+	let map = Map::from_json(&data).ok()?;
+	let hash = map.hash_str();
+	set_map(map);
+	Some(hash)
+}
+
+#[wasm_bindgen]
+#[cfg(target_arch = "wasm32")]
+pub fn map_hash() -> String {
+	current_map().hash_str()
+}
@@ -0,0 +1,315 @@
+use crate::protocol::packed;
+use crate::trajectory::*;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+
+//What the server and its clients tell each other, and what the messages carry. Both sides
+//build these, the client through encode_message and decode_message in protocol.rs.
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum LootContent {
+	Cash(u32),
+	PistolAmmo(u32),
+	Health(u8),
+	Fuel(u8), //percentage of a full tank
+	SpeedBoost,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LootObject {
+	pub x: f32,
+	pub y: f32,
+	pub loot: LootContent,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Color {
+	pub r: i32,
+	pub g: i32,
+	pub b: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Weapon {
+	pub weptype: WeaponType,
+	pub ammo: u32,
+}
+
+#[derive(Debug, Clone)]
+pub enum WeaponType {
+	Pistol,
+	Grenade { press_time: u64 }, //when the trigger was pressed, in trajectory time
+	Blaster,
+}
+
+impl Serialize for WeaponType {
+	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+	where
+		S: Serializer,
+	{
+		match self {
+			WeaponType::Pistol => serializer.serialize_str("Pistol"),
+			WeaponType::Grenade { .. } => serializer.serialize_str("Grenade"),
+			WeaponType::Blaster => serializer.serialize_str("Blaster"),
+		}
+	}
+}
+
+//when the trigger was pressed is only known to the server
+impl<'de> Deserialize<'de> for WeaponType {
+	fn deserialize<D>(deserializer: D) -> Result<WeaponType, D::Error>
+	where
+		D: Deserializer<'de>,
+	{
+		match String::deserialize(deserializer)?.as_str() {
+			"Pistol" => Ok(WeaponType::Pistol),
+			"Grenade" => Ok(WeaponType::Grenade { press_time: 0 }),
+			"Blaster" => Ok(WeaponType::Blaster),
+			other => Err(D::Error::unknown_variant(
+				other,
+				&["Pistol", "Grenade", "Blaster"],
+			)),
+		}
+	}
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Inventory {
+	pub selection: u8,
+	#[serde(with = "slots")]
+	pub weapons: HashMap<u8, Weapon>,
+}
+
+//the slots are written as text, javascript objects only have text keys
+mod slots {
+	use super::Weapon;
+	use serde::de::Error;
+	use serde::{Deserialize, Deserializer, Serializer};
+	use std::collections::HashMap;
+
+	pub fn serialize<S>(weapons: &HashMap<u8, Weapon>, serializer: S) -> Result<S::Ok, S::Error>
+	where
+		S: Serializer,
+	{
+		serializer.collect_map(
+			weapons
+				.iter()
+				.map(|(slot, weapon)| (slot.to_string(), weapon)),
+		)
+	}
+
+	pub fn deserialize<'de, D>(deserializer: D) -> Result<HashMap<u8, Weapon>, D::Error>
+	where
+		D: Deserializer<'de>,
+	{
+		HashMap::<String, Weapon>::deserialize(deserializer)?
+			.into_iter()
+			.map(|(slot, weapon)| Ok((slot.parse().map_err(D::Error::custom)?, weapon)))
+			.collect()
+	}
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LootDrop {
+	pub uuid: String,
+	pub object: LootObject,
+}
+
+//a player as clients are told about it, only players themselves get to know what they carry
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PlayerView {
+	pub name: String,
+	pub id: String,
+	pub color: Color,
+	#[serde(with = "packed")]
+	pub trajectory: Trajectory,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub inventory: Option<Inventory>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub cash: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Victim {
+	pub id: String,
+	pub hash: String,
+	pub time: u64,
+
+	//set by the server when the shot killed, whatever a client sent
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub loot: Option<LootDrop>,
+}
+
+//a ship damaged by a grenade's blast or a blaster bolt
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProjectileHit {
+	pub id: String,
+	pub damage: u8,
+
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub loot: Option<LootDrop>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ShootInfo {
+	//details about when the shooter shot
+	pub at: String,
+	pub stime: u64,
+
+	//These are for when the message comes from the server
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub shooter: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub victim: Option<Victim>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "t", content = "c")]
+pub enum ClientMessage {
+	Ping,
+	AckPong,
+	TrajectoryUpdate {
+		change: UpdateTypeWrapper,
+		at: String,
+		time: u64,
+	},
+	ChangeSlot {
+		slot: u8,
+	},
+	ClaimLoot {
+		loot_id: String,
+	},
+	Correct(String),
+	TriggerPress {
+		time: u64,
+	},
+	Shoot(ShootInfo), //fires the selected weapon, grenades are thrown when the trigger is released
+	StateQuery,       //asks for the next snapshot to list everything
+	AckSnapshot {
+		sequence: u64,
+	},
+	Spawn,
+}
+
+impl ClientMessage {
+	//every name there is, so that limits can't be set for messages that don't exist
+	pub const NAMES: [&'static str; 11] = [
+		"Ping",
+		"AckPong",
+		"TrajectoryUpdate",
+		"ChangeSlot",
+		"ClaimLoot",
+		"Correct",
+		"TriggerPress",
+		"Shoot",
+		"StateQuery",
+		"AckSnapshot",
+		"Spawn",
+	];
+
+	//the tag it is sent with, also what its limits go by. A new message goes in NAMES too.
+	pub fn name(&self) -> &'static str {
+		match self {
+			ClientMessage::Ping => "Ping",
+			ClientMessage::AckPong => "AckPong",
+			ClientMessage::TrajectoryUpdate { .. } => "TrajectoryUpdate",
+			ClientMessage::ChangeSlot { .. } => "ChangeSlot",
+			ClientMessage::ClaimLoot { .. } => "ClaimLoot",
+			ClientMessage::Correct(_) => "Correct",
+			ClientMessage::TriggerPress { .. } => "TriggerPress",
+			ClientMessage::Shoot(_) => "Shoot",
+			ClientMessage::StateQuery => "StateQuery",
+			ClientMessage::AckSnapshot { .. } => "AckSnapshot",
+			ClientMessage::Spawn => "Spawn",
+		}
+	}
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "t", content = "c")]
+pub enum ServerMessage {
+	Pong(u64),
+	PlayerJoin(PlayerView),
+	PlayerLeave(String),
+	HealthUpdate(u8),
+	//What the client can see. Unless base is None it only lists what changed since the
+	//acknowledged snapshot base: the players that are new or changed, and the loot that is new.
+	GameState {
+		sequence: u64,
+		base: Option<u64>,
+		players: Vec<PlayerView>,
+		loot: HashMap<String, LootObject>,
+		gone: Vec<String>,  //players
+		taken: Vec<String>, //loot
+	},
+	//players and loot that came within view, clients are only told about what they can see
+	EnterView {
+		players: Vec<PlayerView>,
+		loot: HashMap<String, LootObject>,
+	},
+	LeaveView {
+		players: Vec<String>,
+		loot: Vec<String>,
+	},
+	TrajectoryUpdate {
+		change: UpdateTypeWrapper,
+		time: u64,
+		at: String,
+		from: String,
+	},
+	Shoot(ShootInfo),
+	PlayerDeath {
+		loot: Option<LootDrop>,
+		from: String,
+	},
+	LootCollected {
+		loot_id: String,
+		collector: String,
+	},
+	Correct {
+		id: String,
+		#[serde(with = "packed")]
+		tr: Trajectory,
+	},
+	LootReject(String),
+	ShipContact {
+		first: String,
+		second: String,
+		damage: u8,
+	},
+	ShotRejected {
+		reason: String,
+	},
+	Warning(String), //the player is under suspicion of cheating
+	GrenadeThrown {
+		id: String,
+		thrower: String,
+		#[serde(with = "packed")]
+		flight: Trajectory, //clients step it like a ship's
+		fuse_end: u64,
+	},
+	GrenadeExploded {
+		id: String,
+		x: f32,
+		y: f32,
+		time: u64,
+		hits: Vec<ProjectileHit>,
+	},
+	BulletFired {
+		id: String,
+		shooter: String,
+		#[serde(with = "packed")]
+		flight: Trajectory, //stepped by clients like a grenade's
+		expires: u64,
+	},
+	BulletImpact {
+		id: String,
+		x: f32,
+		y: f32,
+		time: u64,
+		hit: Option<ProjectileHit>, //None when it hit a body or faded
+	},
+}
@@ -0,0 +1,138 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+#[cfg(target_arch = "wasm32")]
+use crate::messages::{ClientMessage, ServerMessage};
+
+//How messages travel over the websocket. JSON text frames are what every client understands,
+//clients that can also offer the binary protocol: the same messages as CBOR in binary frames,
+//behind a byte with the protocol's version. Trajectories in them are their bincode bytes, in
+//JSON base64 of those. Which one is used is agreed on when connecting, the client lists the
+//websocket subprotocols it speaks in its preferred order and the server picks the first it
+//knows. A client that lists none gets JSON.
+
+pub const PROTOCOL_VERSION: u8 = 2; //bump when the binary framing or the messages change
+const JSON_PROTOCOL: &str = "json";
+const BINARY_PROTOCOL: &str = "binary";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Codec {
+	#[default]
+	Json,
+	Binary,
+}
+
+impl Codec {
+	//the subprotocol it goes by, binary ones carry the version
+	pub fn name(&self) -> String {
+		match self {
+			Codec::Json => JSON_PROTOCOL.to_string(),
+			Codec::Binary => format!("{}.{}", BINARY_PROTOCOL, PROTOCOL_VERSION),
+		}
+	}
+
+	//the first of the comma separated subprotocols that this side speaks
+	pub fn negotiate(offered: &str) -> Option<Codec> {
+		offered.split(',').map(str::trim).find_map(|name| {
+			[Codec::Binary, Codec::Json]
+				.into_iter()
+				.find(|codec| codec.name() == name)
+		})
+	}
+}
+
+pub fn to_binary<T: Serialize + ?Sized>(message: &T) -> Result<Vec<u8>, String> {
+	let mut frame = vec![PROTOCOL_VERSION];
+	ciborium::into_writer(message, &mut frame)
+		.map_err(|e| format!("Can't encode message: {}", e))?;
+	Ok(frame)
+}
+
+pub fn from_binary<T: DeserializeOwned>(frame: &[u8]) -> Result<T, String> {
+	match frame.split_first() {
+		Some((&PROTOCOL_VERSION, body)) => {
+			ciborium::from_reader(body).map_err(|e| format!("Can't decode message: {}", e))
+		}
+		Some((version, _)) => Err(format!(
+			"Message has protocol version {} but {} is spoken here",
+			version, PROTOCOL_VERSION
+		)),
+		None => Err("Empty message".to_string()),
+	}
+}
+
+//the subprotocol to offer for the binary protocol
+#[wasm_bindgen]
+pub fn binary_protocol() -> String {
+	Codec::Binary.name()
+}
+
+//a client message given as an object like {t: "Ping"} turned into a binary frame
+#[wasm_bindgen]
+#[cfg(target_arch = "wasm32")]
+pub fn encode_message(message: JsValue) -> Result<Vec<u8>, JsError> {
+	let message: ClientMessage = serde_wasm_bindgen::from_value(message)?;
+	to_binary(&message).map_err(|e| JsError::new(&e))
+}
+
+//A binary frame from the server as the same object JSON.parse makes of a text frame, so
+//trajectories are given as base64 for the Trajectory constructor.
+#[wasm_bindgen]
+#[cfg(target_arch = "wasm32")]
+pub fn decode_message(frame: &[u8]) -> Result<JsValue, JsError> {
+	let message: ServerMessage = from_binary(frame).map_err(|e| JsError::new(&e))?;
+	Ok(message.serialize(&serde_wasm_bindgen::Serializer::json_compatible())?)
+}
+
+//for #[serde(with = "packed")] on the trajectories in messages
+pub mod packed {
+	use crate::trajectory::Trajectory;
+	use base64::{engine::general_purpose, Engine as _};
+	use serde::de::{Error, Visitor};
+	use serde::{Deserializer, Serializer};
+	use std::fmt;
+
+	pub fn serialize<S>(trajectory: &Trajectory, serializer: S) -> Result<S::Ok, S::Error>
+	where
+		S: Serializer,
+	{
+		let bytes = bincode::serialize(trajectory).map_err(serde::ser::Error::custom)?;
+		if serializer.is_human_readable() {
+			serializer.serialize_str(&general_purpose::STANDARD.encode(bytes))
+		} else {
+			serializer.serialize_bytes(&bytes)
+		}
+	}
+
+	pub fn deserialize<'de, D>(deserializer: D) -> Result<Trajectory, D::Error>
+	where
+		D: Deserializer<'de>,
+	{
+		let bytes = if deserializer.is_human_readable() {
+			deserializer.deserialize_str(Packed)?
+		} else {
+			deserializer.deserialize_bytes(Packed)?
+		};
+		bincode::deserialize(&bytes).map_err(D::Error::custom)
+	}
+
+	//a trajectory's bytes, either as they are or in base64
+	struct Packed;
+
+	impl<'de> Visitor<'de> for Packed {
+		type Value = Vec<u8>;
+
+		fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+			formatter.write_str("a trajectory's bytes or base64 of them")
+		}
+
+		fn visit_str<E: Error>(self, text: &str) -> Result<Vec<u8>, E> {
+			general_purpose::STANDARD.decode(text).map_err(E::custom)
+		}
+
+		fn visit_bytes<E: Error>(self, bytes: &[u8]) -> Result<Vec<u8>, E> {
+			Ok(bytes.to_vec())
+		}
+	}
+}
@@ -0,0 +1,90 @@
+//The number type used by the physics. It is f32 by default and the 32.32 fixed point
+//type with the fixed-point feature. Both server and client must be built the same way.
+
+use crate::fixed::Fixed;
+use std::f32::consts::PI;
+
+#[cfg(feature = "fixed-point")]
+pub type Scalar = Fixed;
+
+#[cfg(not(feature = "fixed-point"))]
+pub type Scalar = f32;
+
+//name of the backend, part of the hash version since the two never produce the same hashes
+#[cfg(feature = "fixed-point")]
+pub const BACKEND: &str = "fixed";
+
+#[cfg(not(feature = "fixed-point"))]
+pub const BACKEND: &str = "f32";
+
+const TWOPI: f32 = 2f32 * PI;
+
+//conversions and trigonometry that both backends provide with the same meaning
+pub trait Real: Copy {
+	fn from_f32(value: f32) -> Self;
+	fn to_f32(self) -> f32;
+	fn from_ratio(num: u64, den: u64) -> Self;
+	fn cos_det(self) -> Self;
+	fn sin_det(self) -> Self;
+	fn hash_bits(self) -> u64; //the exact representation, widened to 64 bits
+}
+
+//fastapprox is plain arithmetic, so unlike the std trig functions it gives
+//the same bits on native and wasm
+impl Real for f32 {
+	fn from_f32(value: f32) -> f32 {
+		value
+	}
+
+	fn to_f32(self) -> f32 {
+		self
+	}
+
+	fn from_ratio(num: u64, den: u64) -> f32 {
+		num as f32 / den as f32
+	}
+
+	fn cos_det(self) -> f32 {
+		-fastapprox::faster::cos(normalize_angle(self))
+	}
+
+	fn sin_det(self) -> f32 {
+		fastapprox::faster::sin(normalize_angle(self))
+	}
+
+	fn hash_bits(self) -> u64 {
+		self.to_bits() as u64
+	}
+}
+
+impl Real for Fixed {
+	fn from_f32(value: f32) -> Fixed {
+		Fixed::from_f64(value as f64)
+	}
+
+	fn to_f32(self) -> f32 {
+		self.to_f64() as f32
+	}
+
+	fn from_ratio(num: u64, den: u64) -> Fixed {
+		Fixed::from_ratio(num, den)
+	}
+
+	fn cos_det(self) -> Fixed {
+		self.cos()
+	}
+
+	fn sin_det(self) -> Fixed {
+		self.sin()
+	}
+
+	fn hash_bits(self) -> u64 {
+		self.to_bits() as u64
+	}
+}
+
+//maps x to pi - (x mod 2pi), so cos flips sign and sin stays the same
+fn normalize_angle(x: f32) -> f32 {
+	let modded = ((x % TWOPI) + TWOPI) % TWOPI;
+	PI - modded
+}
@@ -1,183 +1,106 @@
-use crate::shared_gameobjects::ShopItemId;
+use crate::history::TrajectoryHistory;
+use crate::messages::*;
 use crate::trajectory::*;
-use serde::{Deserialize, Serialize, Serializer};
-use serde_json::{json, Value};
-use std::collections::HashMap;
-
-#[derive(Serialize, Debug, Clone)]
-pub enum LootContent {
-	Cash(u32),
-	PistolAmmo(u32),
-	Health(u8),
-	SpeedBoost,
-}
-
-#[derive(Serialize, Debug, Clone)]
-pub struct LootObject {
-	pub x: f32,
-	pub y: f32,
-	pub loot: LootContent,
-}
-
-#[derive(Serialize, Debug, Clone)]
-pub struct Color {
-	pub r: i32,
-	pub g: i32,
-	pub b: i32,
-}
-
-#[derive(Serialize, Debug, Clone)]
-pub struct Weapon {
-	pub weptype: WeaponType,
-	pub ammo: u32,
-}
 
 #[derive(Debug, Clone)]
-pub enum WeaponType {
-	Pistol,
-	Grenade { press_time: f32 },
-}
-
-impl Serialize for WeaponType {
-	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-	where
-		S: Serializer,
-	{
-		match self {
-			WeaponType::Pistol => serializer.serialize_str("Pistol"),
-			WeaponType::Grenade { press_time: _ } => serializer.serialize_str("Grenade"),
-		}
-	}
-}
-
-#[derive(Serialize, Debug, Clone)]
-pub struct Inventory {
-	pub selection: u8,
-	pub weapons: HashMap<u8, Weapon>,
-}
-
-#[derive(Serialize, Debug, Clone)]
-pub struct LootDrop {
-	pub uuid: String,
-	pub object: LootObject,
-}
-
-#[derive(Debug, Clone, Serialize)]
 pub struct PlayerState {
 	pub name: String,
 	pub id: String,
 	pub cash: u32,
-	pub fuel: u32,
 	pub color: Color,
 	pub inventory: Inventory,
 	pub trigger_pressed: bool,
-	#[serde(skip_serializing)]
 	pub trajectory: Trajectory,
+	pub history: TrajectoryHistory,
+	pub death_announced: bool, //the others were told this life is over
 }
 
 impl PlayerState {
-	pub fn encode_other(&self) -> Value {
-		//TODO consider implementing live() in Trajectory - an immutable version of reset() and use that instead
-		return json!({
-			"name": &self.name,
-			"id": &self.id,
-			"color": &self.color,
-			"trajectory": &self.trajectory.to_b64(),
-		});
+	//call after anything but stepping changed the trajectory, so it can be rebuilt later
+	pub fn record(&mut self, change: Option<UpdateTypeWrapper>) {
+		self.history.record(&self.trajectory, change);
 	}
 
-	pub fn encode(&self, as_self: bool) -> Value {
-		if !as_self {
-			return self.encode_other();
-		}
-		let mut result = self.encode_other();
-		let additional = json!({
-			"inventory": &self.inventory,
-			"cash": &self.cash,
-		});
-		result
-			.as_object_mut()
-			.unwrap()
-			.extend(additional.as_object().unwrap().clone());
-		return result;
+	pub fn checkpoint(&mut self) {
+		self.history.checkpoint(&self.trajectory);
 	}
-}
-
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct Victim {
-	pub id: String,
-	pub hash: String,
-	pub time: u64,
 
-	#[serde(skip_serializing_if = "Option::is_none")]
-	#[serde(skip_deserializing)]
-	pub loot: Option<LootDrop>,
-}
+	//Whether the player died without the others being told, only the first time it is asked.
+	//Whatever advanced the ship into a body, it is announced once.
+	pub fn announce_death(&mut self) -> bool {
+		let untold = self.trajectory.health == 0 && !self.death_announced;
+		self.death_announced |= untold;
+		untold
+	}
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct ShootInfo {
-	//details about when the shooter shot
-	pub at: String,
-	pub stime: u64,
+	//where the player was at the given time, None if the history doesn't reach back that far
+	pub fn trajectory_at(&self, time: u64) -> Option<Trajectory> {
+		self.history.at(time)
+	}
 
-	//These are for when the message comes from the server
-	#[serde(skip_serializing_if = "Option::is_none")]
-	pub shooter: Option<String>,
-	#[serde(skip_serializing_if = "Option::is_none")]
-	pub victim: Option<Victim>,
+	//how the player is sent to clients, only players themselves are told what they carry
+	pub fn view(&self, as_self: bool) -> PlayerView {
+		//TODO consider implementing live() in Trajectory - an immutable version of reset() and use that instead
+		PlayerView {
+			name: self.name.clone(),
+			id: self.id.clone(),
+			color: self.color.clone(),
+			trajectory: self.trajectory.clone(),
+			inventory: as_self.then(|| self.inventory.clone()),
+			cash: as_self.then_some(self.cash),
+		}
+	}
 }
 
-#[derive(Deserialize, Debug)]
-#[serde(tag = "t", content = "c")]
-pub enum ClientMessage {
-	Ping,
-	AckPong,
-	TrajectoryUpdate {
-		change: UpdateTypeWrapper,
-		at: String,
-		time: u64,
-	},
-	ChangeSlot {
-		slot: u8,
-	},
-	ClaimLoot {
-		loot_id: String,
-	},
-	Correct(String),
-	Shoot(ShootInfo),
-	StateQuery,
-	Spawn,
+//the shooter at the time of the shot, rebuilt from history and compared with the hash sent
+pub fn validate_shooter(
+	shooter: &TrajectoryHistory,
+	shot: &ShootInfo,
+	now: u64,
+) -> Result<Trajectory, String> {
+	if shot.stime + MAX_TIME_BEFORE < now || shot.stime > now + MAX_TIME_AHEAD {
+		return Err(format!(
+			"Shot at {} is too far from now ({})",
+			shot.stime, now
+		));
+	}
+	let shooter = shooter
+		.at(shot.stime)
+		.ok_or("Shot is older than the shooter's history")?;
+	if shooter.hash_str() != shot.at {
+		return Err("Shooter hash mismatch".to_string());
+	}
+	Ok(shooter)
 }
 
-#[derive(Serialize, Debug)]
-#[serde(tag = "t", content = "c")]
-pub enum ServerMessage {
-	Pong(u64),
-	PlayerJoin(PlayerState),
-	PlayerLeave(String),
-	HealthUpdate(u8),
-	GameState {
-		pstates: Vec<PlayerState>,
-		worldloot: HashMap<String, LootObject>,
-	},
-	TrajectoryUpdate {
-		change: UpdateTypeWrapper,
-		time: u64,
-		at: String,
-		from: String,
-	},
-	Shoot(ShootInfo),
-	PlayerDeath {
-		loot: Option<LootDrop>,
-		from: String,
-	},
-	LootCollected {
-		loot_id: String,
-		collector: String,
-	},
-	Correct {
-		id: String,
-		tr: String,
-	},
-	LootReject(String),
+//Checks a hit the way the shooter's client saw it: the shooter at the time of the shot and the
+//victim at the earlier time it was being shown, both rebuilt from history and compared with
+//the hashes the client sent. Returns the distance of the hit or why it can't have happened.
+pub fn validate_hit(
+	shooter: &TrajectoryHistory,
+	shot: &ShootInfo,
+	victim: &Victim,
+	target: &TrajectoryHistory,
+	now: u64,
+) -> Result<f32, String> {
+	let shooter = validate_shooter(shooter, shot, now)?;
+	if victim.time > shot.stime || shot.stime - victim.time > MAX_TIME_BEFORE {
+		return Err(format!(
+			"Victim seen at {} can't be hit at {}",
+			victim.time, shot.stime
+		));
+	}
+	let target = target
+		.at(victim.time)
+		.ok_or("Victim is older than its history")?;
+	if target.hash_str() != victim.hash {
+		return Err("Victim hash mismatch".to_string());
+	}
+	if target.health == 0 {
+		return Err("Victim was already dead".to_string());
+	}
+	match shooter.hits(&target) {
+		distance if distance > 0.0 => Ok(distance),
+		_ => Err("Missed".to_string()),
+	}
 }
@@ -8,6 +8,7 @@ pub enum ShopItemId {
 	MoreBoosters,
 	Health,
 	Ammunition,
+	Fuel,
 }
 
 #[wasm_bindgen]
@@ -33,7 +34,19 @@ impl ShopItem {
 	}
 }
 
-pub fn get_shop_items() -> [ShopItem; 3] {
+#[wasm_bindgen]
+impl ShopItem {
+	//the trajectory change that buying this item makes, if it is bought that way
+	pub fn update_type(&self) -> Option<UpdateType> {
+		match self.id {
+			ShopItemId::MoreBoosters => Some(UpdateType::AddBoost),
+			ShopItemId::Fuel => Some(UpdateType::Refuel),
+			_ => None,
+		}
+	}
+}
+
+pub fn get_shop_items() -> [ShopItem; 4] {
 	[
 		ShopItem {
 			cost: 5,
@@ -65,6 +78,16 @@ pub fn get_shop_items() -> [ShopItem; 3] {
 			#[cfg(target_arch = "wasm32")]
 			image_src: "static/textures/pistol_ammo.png".to_string(),
 		},
+		ShopItem {
+			cost: 5,
+			id: ShopItemId::Fuel,
+
+			#[cfg(target_arch = "wasm32")]
+			display_name: "full tank".to_string(),
+
+			#[cfg(target_arch = "wasm32")]
+			image_src: "static/textures/speed_boost.png".to_string(),
+		},
 	]
 }
 
@@ -86,6 +109,7 @@ pub fn num_shop_items() -> usize {
 pub fn get_cost(utype: UpdateType) -> u32 {
 	match utype {
 		UpdateType::AddBoost => 5,
+		UpdateType::Refuel => 5,
 		_ => 0,
 	}
 }
@@ -0,0 +1,231 @@
+use std::collections::VecDeque;
+
+use crate::scalar::Real;
+use crate::trajectory::{
+	State, Trajectory, UpdateType, UpdateTypeWrapper, MAX_TIME_BEFORE, TIMESTEP_MILLIS,
+};
+
+//What a client keeps about a ship besides its current state, so news about the past can be
+//fitted in. The last state the server vouched for is kept with every change after it, whether
+//the player made it here or another client sent it. A late change or a correction from the
+//server rebuilds the ship from there with the changes replayed on top, and the gap between
+//where it was drawn and where it turned out to be fades out instead of showing as a jump.
+
+const SMOOTHING: u64 = 300; //millis for a rebuilt ship to glide onto its new path
+const SNAP_DISTANCE: f32 = 200.0; //further off than this is shown at once
+
+#[derive(Debug, Clone)]
+pub struct Input {
+	pub time: u64,
+	pub hash: Option<String>, //the sender's state before it, None for changes made here
+	pub change: UpdateTypeWrapper,
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+#[derive(Clone)]
+pub struct Timeline {
+	base: Trajectory, //vouched for by the server, or the oldest state still worth keeping
+	inputs: VecDeque<Input>, //at or after the base, in time order
+	applied: usize,   //how many of the inputs the current state went through
+	error: (f32, f32), //drawn minus rebuilt position at error_time
+	error_time: u64,
+}
+
+impl Timeline {
+	pub fn new(start: &Trajectory) -> Timeline {
+		Timeline {
+			base: start.detached(),
+			inputs: VecDeque::new(),
+			applied: 0,
+			error: (0.0, 0.0),
+			error_time: start.time,
+		}
+	}
+
+	//a change made here to the current state, it is applied right away
+	pub fn record(&mut self, current: &mut Trajectory, change: UpdateTypeWrapper) {
+		let input = Input {
+			time: current.time,
+			hash: None,
+			change,
+		};
+		current.apply_change(input.change.clone());
+		self.inputs.insert(self.applied, input);
+		self.applied += 1;
+	}
+
+	//Fits in a change made elsewhere. If the current state already went past its time it is
+	//rebuilt, otherwise the change waits until advance gets there. On an error, the input
+	//being too old or not matching the state, nothing changes.
+	pub fn insert(&mut self, current: &mut Trajectory, input: Input) -> Result<(), String> {
+		if input.time < self.base.time {
+			return Err(format!(
+				"Input at {} is older than the history at {}",
+				input.time, self.base.time
+			));
+		}
+		let index = self
+			.inputs
+			.partition_point(|other| other.time <= input.time);
+		let late = input.time < current.time || index < self.applied;
+		self.inputs.insert(index, input);
+		if !late {
+			return Ok(());
+		}
+		match self.replay(current.time) {
+			Ok((rebuilt, applied)) => {
+				self.rebuilt(current, rebuilt, applied);
+				Ok(())
+			}
+			Err(error) => {
+				self.inputs.remove(index);
+				Err(error)
+			}
+		}
+	}
+
+	//Steps the current state up to time, applying the inputs as it gets to them. Returns the
+	//changes that happened, including running out of fuel.
+	pub fn advance(
+		&mut self,
+		current: &mut Trajectory,
+		time: u64,
+	) -> Result<Vec<UpdateType>, String> {
+		let (mut applied, mut changes) = (self.applied, Vec::new());
+		let played = self.play(current, &mut applied, time, &mut changes);
+		self.applied = applied;
+		played?;
+		self.settle(current.time);
+		Ok(changes)
+	}
+
+	//Takes the server's state as the new base. Inputs it already includes are dropped and the
+	//rest are replayed on top of it, all of them if none came from elsewhere.
+	pub fn correct(&mut self, current: &mut Trajectory, authoritative: &Trajectory) {
+		if authoritative.time < self.base.time {
+			return; //the changes it would need replayed are already folded into the base
+		}
+		let hash = authoritative.hash_str();
+		self.inputs.retain(|input| {
+			input.time > authoritative.time
+				|| (input.time == authoritative.time && input.hash.as_ref() == Some(&hash))
+		});
+		self.base = authoritative.detached();
+		let time = current.time.max(self.base.time);
+		let replayed = self.replay(time).or_else(|_| {
+			//what was sent from elsewhere doesn't fit the server's state, the server knows better
+			self.inputs.clear();
+			self.replay(time)
+		});
+		if let Ok((rebuilt, applied)) = replayed {
+			self.rebuilt(current, rebuilt, applied);
+		}
+	}
+
+	//where to draw a ship that is at drawn on its current path
+	pub fn smooth(&self, drawn: State, time: u64) -> State {
+		let elapsed = time.saturating_sub(self.error_time);
+		let left = 1.0 - (elapsed as f32 / SMOOTHING as f32).min(1.0);
+		State {
+			x: drawn.x + self.error.0 * left,
+			y: drawn.y + self.error.1 * left,
+			r: drawn.r,
+		}
+	}
+
+	//the inputs the current state has yet to get to
+	pub fn pending(&self) -> impl Iterator<Item = &Input> {
+		self.inputs.range(self.applied..)
+	}
+
+	//the base stepped to time with the inputs replayed, and how many of them it went through
+	fn replay(&self, time: u64) -> Result<(Trajectory, usize), String> {
+		let (mut rebuilt, mut applied) = (self.base.detached(), 0);
+		self.play(&mut rebuilt, &mut applied, time, &mut Vec::new())?;
+		Ok((rebuilt, applied))
+	}
+
+	fn rebuilt(&mut self, current: &mut Trajectory, rebuilt: Trajectory, applied: usize) {
+		let drawn = self.smooth(
+			State {
+				x: current.pos.x.to_f32(),
+				y: current.pos.y.to_f32(),
+				r: 0.0,
+			},
+			current.time,
+		);
+		let error = (
+			drawn.x - rebuilt.pos.x.to_f32(),
+			drawn.y - rebuilt.pos.y.to_f32(),
+		);
+		let distance = (error.0 * error.0 + error.1 * error.1).sqrt();
+		self.error = if distance.is_finite() && distance < SNAP_DISTANCE {
+			error
+		} else {
+			(0.0, 0.0)
+		};
+		self.error_time = current.time.min(rebuilt.time);
+		self.applied = applied;
+		*current = rebuilt;
+	}
+
+	//Steps trajectory up to time, applying the inputs from next on when it gets to their time.
+	//next is left at the first input it didn't get to, also when one doesn't match.
+	fn play(
+		&self,
+		trajectory: &mut Trajectory,
+		next: &mut usize,
+		time: u64,
+		changes: &mut Vec<UpdateType>,
+	) -> Result<(), String> {
+		while trajectory.time + TIMESTEP_MILLIS as u64 <= time {
+			while let Some(input) = self
+				.inputs
+				.get(*next)
+				.filter(|input| input.time <= trajectory.time)
+			{
+				if let Some(hash) = &input.hash {
+					if *hash != trajectory.hash_str() {
+						return Err(format!(
+							"Hash mismatch at {}! request was {} but got {}",
+							input.time,
+							hash,
+							trajectory.hash_str()
+						));
+					}
+				}
+				changes.push(input.change.utype);
+				trajectory.apply_change(input.change.clone());
+				*next += 1;
+			}
+			let was_propelling = trajectory.propelling;
+			if !trajectory.step() {
+				break; //dead
+			}
+			if was_propelling && !trajectory.propelling {
+				changes.push(UpdateType::PropOff); //ran out of fuel
+			}
+		}
+		Ok(())
+	}
+
+	//Moves the base up to where nothing can arrive for anymore, so that replaying never takes
+	//more than a few seconds of steps. The server refuses changes older than MAX_TIME_BEFORE and
+	//its snapshots are about that old, twice that leaves room for them to arrive.
+	fn settle(&mut self, time: u64) {
+		let horizon = time.saturating_sub(2 * MAX_TIME_BEFORE);
+		if self.base.time + MAX_TIME_BEFORE > horizon {
+			return;
+		}
+		let (mut base, mut folded) = (self.base.detached(), 0);
+		if self
+			.play(&mut base, &mut folded, horizon, &mut Vec::new())
+			.is_err() || folded > self.applied
+		{
+			return; //the current state never got past it either
+		}
+		self.inputs.drain(..folded);
+		self.applied -= folded;
+		self.base = base;
+	}
+}
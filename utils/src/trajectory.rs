@@ -1,13 +1,15 @@
 use base64::{engine::general_purpose, Engine as _};
 use serde::{Deserialize, Serialize};
-use serde_json::{json, to_string};
-use std::collections::hash_map::DefaultHasher;
 use std::f32::consts::PI;
-use std::hash::{Hash, Hasher};
+use std::str::FromStr;
 use wasm_bindgen::prelude::*;
+use xxhash_rust::xxh3::xxh3_64;
+
+use crate::map::{current_map, Map, Orbit};
+use crate::scalar::{Real, Scalar, BACKEND};
 
 #[cfg(target_arch = "wasm32")]
-use std::collections::VecDeque;
+use crate::timeline::{Input, Timeline};
 
 #[cfg(target_arch = "wasm32")]
 use bincode::deserialize;
@@ -46,231 +48,84 @@ macro_rules! console_log {
     ($($t:tt)*) => (log(&format_args!($($t)*).to_string()))
 }
 
-const TWOPI: f32 = 2f32 * PI;
 const HALFPI: f32 = PI / 2f32;
 
+//Bump whenever the hashed layout or the result of step changes, clients on another
+//version can't agree with the server on any hash and are turned away when connecting.
+pub const HASH_VERSION: u32 = 6;
+
 pub const PLAYER_RADIUS: f32 = 25.0;
-pub const DOME_RADIUS: f32 = 6000.0;
+pub const MAX_FUEL: u16 = 600; //a minute of propelling with one booster
 
 const ACCELERATION: f32 = 200.0; //player acceleration
 const PROPEL_DIRECTION: f32 = -HALFPI;
 const RADIANS_PER_SECOND: f32 = PI; //player rotation speed
 const G: f32 = 2000.0; //Gravitational constant
 const PISTOL_REACH: f32 = 500.0; //players have circular hitbox
+const FUEL_PER_STEP: u16 = 1; //burnt by each booster while propelling
+const RAM_DAMAGE: f32 = 0.1; //health both ships lose per unit of closing speed
+const ZONE_DAMAGE: u8 = 2; //health lost every step spent entirely outside the dome
 
 //const REGEN:
 const TIMESTEP_FPS: u32 = 10;
 
 //Calculated
-const TIMESTEP_MILLIS: u32 = 1000 / TIMESTEP_FPS;
+pub(crate) const TIMESTEP_MILLIS: u32 = 1000 / TIMESTEP_FPS;
 const TIMESTEP_SECS: f32 = 1f32 / TIMESTEP_FPS as f32;
 
-#[cfg(not(target_arch = "wasm32"))]
+//How far in the past or future clients may date their changes. They aren't among the server's
+//settings on purpose: a client's Timeline::settle folds everything older than twice
+//MAX_TIME_BEFORE into its base, so a server that accepted older changes would send corrections
+//its clients can't replay. Changing them means building the client again.
 pub const MAX_TIME_BEFORE: u64 = 2000; //500
-const MAX_TIME_AHEAD: u64 = 400; //300
+#[cfg(not(target_arch = "wasm32"))]
+pub const MAX_TIME_AHEAD: u64 = 400; //300
 
 #[cfg(not(target_arch = "wasm32"))]
 const SPAWN_PULL_MAX: f32 = 2.0; //Maximum gravity pull at spawn point
 
-pub const BODIES: [Body; 25] = [
-	Body {
-		pos: Vector {
-			x: -2142.0,
-			y: 1319.0,
-		},
-		radius: 82.0,
-	},
-	Body {
-		pos: Vector {
-			x: 593.0,
-			y: 1712.0,
-		},
-		radius: 85.0,
-	},
-	Body {
-		pos: Vector {
-			x: 1630.0,
-			y: 4858.0,
-		},
-		radius: 79.0,
-	},
-	Body {
-		pos: Vector {
-			x: 1123.0,
-			y: -4422.0,
-		},
-		radius: 142.0,
-	},
-	Body {
-		pos: Vector {
-			x: -4643.0,
-			y: 2987.0,
-		},
-		radius: 132.0,
-	},
-	Body {
-		pos: Vector {
-			x: 4568.0,
-			y: 271.0,
-		},
-		radius: 93.0,
-	},
-	Body {
-		pos: Vector {
-			x: 2702.0,
-			y: -553.0,
-		},
-		radius: 116.0,
-	},
-	Body {
-		pos: Vector {
-			x: -479.0,
-			y: 985.0,
-		},
-		radius: 129.0,
-	},
-	Body {
-		pos: Vector {
-			x: -1510.0,
-			y: -2840.0,
-		},
-		radius: 119.0,
-	},
-	Body {
-		pos: Vector {
-			x: 3043.0,
-			y: 3680.0,
-		},
-		radius: 109.0,
-	},
-	Body {
-		pos: Vector {
-			x: -3052.0,
-			y: 2069.0,
-		},
-		radius: 120.0,
-	},
-	Body {
-		pos: Vector {
-			x: 4800.0,
-			y: 4797.0,
-		},
-		radius: 132.0,
-	},
-	Body {
-		pos: Vector {
-			x: -2845.0,
-			y: 1859.0,
-		},
-		radius: 118.0,
-	},
-	Body {
-		pos: Vector {
-			x: -1327.0,
-			y: 4248.0,
-		},
-		radius: 147.0,
-	},
-	Body {
-		pos: Vector {
-			x: 2427.0,
-			y: 4087.0,
-		},
-		radius: 69.0,
-	},
-	Body {
-		pos: Vector {
-			x: 4132.0,
-			y: -3446.0,
-		},
-		radius: 103.0,
-	},
-	Body {
-		pos: Vector {
-			x: -1985.0,
-			y: -1525.0,
-		},
-		radius: 118.0,
-	},
-	Body {
-		pos: Vector {
-			x: 3460.0,
-			y: -1882.0,
-		},
-		radius: 57.0,
-	},
-	Body {
-		pos: Vector {
-			x: 1018.0,
-			y: -1342.0,
-		},
-		radius: 134.0,
-	},
-	Body {
-		pos: Vector { x: 71.0, y: 2422.0 },
-		radius: 145.0,
-	},
-	Body {
-		pos: Vector {
-			x: 2966.0,
-			y: 835.0,
-		},
-		radius: 77.0,
-	},
-	Body {
-		pos: Vector {
-			x: 4558.0,
-			y: -2721.0,
-		},
-		radius: 59.0,
-	},
-	Body {
-		pos: Vector {
-			x: -3210.0,
-			y: 1010.0,
-		},
-		radius: 93.0,
-	},
-	Body {
-		pos: Vector {
-			x: 256.0,
-			y: 3800.0,
-		},
-		radius: 57.0,
-	},
-	Body {
-		pos: Vector {
-			x: 1981.0,
-			y: 3697.0,
-		},
-		radius: 108.0,
-	},
-];
-
+//where a ship is drawn between two steps
 #[wasm_bindgen]
-#[cfg(target_arch = "wasm32")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+#[derive(Clone, Copy)]
 pub struct State {
 	pub x: f32,
 	pub y: f32,
 	pub r: f32,
 }
 
-#[cfg_attr(not(target_arch = "wasm32"), derive(Clone, Debug))]
-#[derive(Deserialize, Serialize)]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+#[derive(Clone, Deserialize, Serialize)]
 #[wasm_bindgen]
 pub struct Trajectory {
 	pub propelling: bool,
 	pub pos: Vector,
 	pub vel: Vector,
-	pub spin: f32,
+	pub spin: Scalar,
 	pub spin_direction: i8, //-1,0,1
 	pub time: u64,
 	pub health: u8,
 	pub boosters: u8,
+	pub fuel: u16,
 
 	#[cfg(target_arch = "wasm32")]
 	#[serde(skip)]
-	updates: VecDeque<TrajectoryUpdate>,
+	timeline: Option<Box<Timeline>>,
+}
+
+//where a trajectory goes if its inputs don't change
+#[derive(Serialize, Debug, Clone)]
+pub struct Prediction {
+	pub path: Vec<f32>, //position after every step, flattened as [x0, y0, x1, y1, ...]
+	pub crash: Option<Crash>,
+}
+
+//position at the end of the step in which the ship hit a body
+#[derive(Serialize, Debug, Clone, Copy)]
+pub struct Crash {
+	pub x: f32,
+	pub y: f32,
+	pub time: u64,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy)]
@@ -284,6 +139,7 @@ pub enum UpdateType {
 	AddBoost,
 	Bullet,
 	Health,
+	Refuel, //value is the percentage of a full tank, a full tank if missing
 }
 
 #[wasm_bindgen]
@@ -298,19 +154,42 @@ pub struct UpdateTypeWrapper {
 impl UpdateTypeWrapper {
 	#[wasm_bindgen(constructor)]
 	pub fn new(utype: UpdateType, value: Option<u8>) -> Self {
-		Self {
-			utype: utype,
-			value: value,
+		Self { utype, value }
+	}
+}
+
+//How step moves a ship through gravity, chosen by the map. Euler is what step always did, it
+//is only first order so a circular orbit wobbles by about a percent of its radius. Verlet
+//(kick-drift-kick) and leapfrog (drift-kick-drift) are second order and keep it within a
+//hundredth of that, leapfrog reads gravity once a step instead of twice.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Integrator {
+	Euler,
+	#[default]
+	Verlet,
+	Leapfrog,
+}
+
+impl Integrator {
+	pub fn name(&self) -> &'static str {
+		match self {
+			Integrator::Euler => "euler",
+			Integrator::Verlet => "verlet",
+			Integrator::Leapfrog => "leapfrog",
 		}
 	}
 }
 
-#[cfg(target_arch = "wasm32")]
-#[derive(Serialize, Debug, Clone)]
-struct TrajectoryUpdate {
-	time: u64,
-	hash: String,
-	change: UpdateTypeWrapper,
+impl FromStr for Integrator {
+	type Err = String;
+
+	fn from_str(name: &str) -> Result<Integrator, String> {
+		[Integrator::Euler, Integrator::Verlet, Integrator::Leapfrog]
+			.into_iter()
+			.find(|integrator| integrator.name() == name)
+			.ok_or_else(|| format!("Unknown integrator {}, use euler, verlet or leapfrog", name))
+	}
 }
 
 impl Trajectory {
@@ -319,15 +198,24 @@ impl Trajectory {
 		general_purpose::STANDARD.encode(serialize(&self).unwrap())
 	}
 
-	pub fn pull_sum(pos: &Vector) -> Vector {
-		let mut pull = Vector { x: 0.0, y: 0.0 };
-		for body in BODIES {
-			let bod_pull = body.pull(&pos);
+	//exact sum over every body, what the map's gravity grid approximates
+	pub fn pull_sum(bodies: &[Body], pos: &Vector) -> Vector {
+		let mut pull = Vector::default();
+		for body in bodies {
+			let bod_pull = body.pull(pos);
 			pull.x += bod_pull.x;
 			pull.y += bod_pull.y;
 		}
-		pull.x *= TIMESTEP_SECS;
-		pull.y *= TIMESTEP_SECS;
+		pull.x *= Scalar::from_f32(TIMESTEP_SECS);
+		pull.y *= Scalar::from_f32(TIMESTEP_SECS);
+		pull
+	}
+
+	//the velocity change over one step, bodies are the map's bodies at the current time
+	pub fn pull_at(map: &Map, bodies: &[Body], pos: &Vector) -> Vector {
+		let mut pull = map.gravity().acceleration(bodies, pos);
+		pull.x *= Scalar::from_f32(TIMESTEP_SECS);
+		pull.y *= Scalar::from_f32(TIMESTEP_SECS);
 		pull
 	}
 
@@ -335,35 +223,221 @@ impl Trajectory {
 		if self.health == 0 {
 			return false;
 		}
-		let next_pos = Vector {
-			x: self.pos.x + self.vel.x * TIMESTEP_SECS,
-			y: self.pos.y + self.vel.y * TIMESTEP_SECS,
-		};
-		if next_pos.x.powi(2) + next_pos.y.powi(2) > DOME_RADIUS.powi(2) {
-			self.vel.reflect(&self.pos);
-			self.vel.x *= 0.5;
-			self.vel.y *= 0.5;
-		} else {
-			self.pos = next_pos;
-		}
-		let pull = Trajectory::pull_sum(&self.pos);
-		self.vel.x += pull.x;
-		self.vel.y += pull.y;
-		self.spin += (self.spin_direction as f32) * RADIANS_PER_SECOND * TIMESTEP_SECS;
-		let magnitude = ACCELERATION * TIMESTEP_SECS * (self.boosters as f32);
-		if self.propelling {
-			self.vel.x +=
-				-fastapprox::faster::cos(normalize_angle(self.spin + PROPEL_DIRECTION)) * magnitude;
-			self.vel.y +=
-				fastapprox::faster::sin(normalize_angle(self.spin + PROPEL_DIRECTION)) * magnitude;
+		let map = current_map();
+		let dt = Scalar::from_f32(TIMESTEP_SECS);
+		let half = Scalar::from_f32(0.5);
+		let prev_pos = self.pos;
+		self.spin += Scalar::from_f32(self.spin_direction as f32)
+			* Scalar::from_f32(RADIANS_PER_SECOND)
+			* dt;
+		let thrust = self.burn();
+		let dome_radius = map.dome_radius_at(self.time);
+		let bodies_before = map.bodies_at(self.time);
+		let bodies_after = map.bodies_at(self.time + TIMESTEP_MILLIS as u64);
+		match map.integrator {
+			Integrator::Euler => {
+				self.drift(dome_radius, dt);
+				self.kick(&Trajectory::pull_at(&map, &bodies_before, &self.pos), None);
+				if let Some(thrust) = thrust {
+					self.kick(&thrust, None);
+				}
+			}
+			Integrator::Verlet => {
+				self.kick(
+					&Trajectory::pull_at(&map, &bodies_before, &self.pos),
+					Some(half),
+				);
+				if let Some(thrust) = thrust {
+					self.kick(&thrust, Some(half));
+				}
+				self.drift(dome_radius, dt);
+				self.kick(
+					&Trajectory::pull_at(&map, &bodies_after, &self.pos),
+					Some(half),
+				);
+				if let Some(thrust) = thrust {
+					self.kick(&thrust, Some(half));
+				}
+			}
+			Integrator::Leapfrog => {
+				self.drift(dome_radius, dt * half);
+				let middle = map.bodies_at(self.time + TIMESTEP_MILLIS as u64 / 2);
+				self.kick(&Trajectory::pull_at(&map, &middle, &self.pos), None);
+				if let Some(thrust) = thrust {
+					self.kick(&thrust, None);
+				}
+				self.drift(dome_radius, dt * half);
+			}
 		}
 		self.time += TIMESTEP_MILLIS as u64;
-		if self.collides() {
+		if self.collides(&map, &prev_pos, &bodies_before, &bodies_after) {
 			self.health = 0;
 		}
+		let zone = dome_radius + Scalar::from_f32(PLAYER_RADIUS);
+		if self.pos.dot(&self.pos) > zone * zone {
+			//left behind by a shrinking dome
+			self.health = self.health.saturating_sub(ZONE_DAMAGE);
+		}
 		true
 	}
 
+	//moves along the velocity for dt, stopping where the path meets the dome instead of
+	//short of it however fast the ship is, and bouncing off it. Ships the dome shrank past
+	//can't get further out but are free to fly back in.
+	fn drift(&mut self, dome_radius: Scalar, dt: Scalar) {
+		let start = self.pos;
+		let end = Vector {
+			x: self.pos.x + self.vel.x * dt,
+			y: self.pos.y + self.vel.y * dt,
+		};
+		let radius_sq = dome_radius * dome_radius;
+		let leaving = start.dot(&start) <= radius_sq || self.vel.dot(&start) > Scalar::default();
+		if end.x * end.x + end.y * end.y > radius_sq && leaving {
+			self.pos = Trajectory::dome_contact(&start, &end, dome_radius);
+			self.vel.reflect(&self.pos);
+			self.vel.x *= Scalar::from_f32(0.5);
+			self.vel.y *= Scalar::from_f32(0.5);
+		} else {
+			self.pos = end;
+		}
+	}
+
+	//adds a velocity change, or the given fraction of it
+	fn kick(&mut self, change: &Vector, fraction: Option<Scalar>) {
+		match fraction {
+			Some(fraction) => {
+				self.vel.x += change.x * fraction;
+				self.vel.y += change.y * fraction;
+			}
+			None => {
+				self.vel.x += change.x;
+				self.vel.y += change.y;
+			}
+		}
+	}
+
+	//the velocity change from propelling over a whole step, burns the fuel for it
+	fn burn(&mut self) -> Option<Vector> {
+		if !self.propelling {
+			return None;
+		}
+		let magnitude = Scalar::from_f32(ACCELERATION)
+			* Scalar::from_f32(TIMESTEP_SECS)
+			* Scalar::from_f32(self.boosters as f32);
+		let direction = self.spin + Scalar::from_f32(PROPEL_DIRECTION);
+		self.fuel = self
+			.fuel
+			.saturating_sub(FUEL_PER_STEP * self.boosters as u16);
+		if self.fuel == 0 {
+			self.propelling = false;
+		}
+		Some(Vector {
+			x: direction.cos_det() * magnitude,
+			y: direction.sin_det() * magnitude,
+		})
+	}
+
+	//runs step on a copy, so the path is exactly where the ship will go
+	pub fn predict(&self, steps: u32) -> Prediction {
+		let mut future = self.clone();
+		let mut prediction = Prediction {
+			path: Vec::with_capacity(2 * steps as usize),
+			crash: None,
+		};
+		for _ in 0..steps {
+			if !future.step() {
+				break;
+			}
+			let (x, y) = (future.pos.x.to_f32(), future.pos.y.to_f32());
+			prediction.path.extend([x, y]);
+			if future.health == 0 {
+				prediction.crash = Some(Crash {
+					x,
+					y,
+					time: future.time,
+				});
+				break;
+			}
+		}
+		prediction
+	}
+
+	//Bounces two touching ships off each other as equal masses and takes health from both by
+	//how fast they were closing in. Both should be within a step of each other in time.
+	//Returns the damage each ship took, None if they don't touch or are already separating.
+	pub fn collide_with(&mut self, other: &mut Trajectory) -> Option<u8> {
+		if self.health == 0 || other.health == 0 {
+			return None;
+		}
+		let zero = Scalar::default();
+		let offset = Vector {
+			x: other.pos.x - self.pos.x,
+			y: other.pos.y - self.pos.y,
+		};
+		let reach = Scalar::from_f32(2.0 * PLAYER_RADIUS);
+		if offset.x.abs() >= reach || offset.y.abs() >= reach {
+			return None; //also keeps the square in mag from saturating in fixed point
+		}
+		let distance = offset.mag();
+		if distance >= reach || distance == zero {
+			return None;
+		}
+		let normal = Vector {
+			x: offset.x / distance,
+			y: offset.y / distance,
+		};
+		let closing = Vector {
+			x: self.vel.x - other.vel.x,
+			y: self.vel.y - other.vel.y,
+		}
+		.dot(&normal);
+		if closing <= zero {
+			return None;
+		}
+		//equal masses swap their velocities along the normal
+		self.vel.x -= normal.x * closing;
+		self.vel.y -= normal.y * closing;
+		other.vel.x += normal.x * closing;
+		other.vel.y += normal.y * closing;
+		//and are moved apart so they don't overlap on the next step
+		let push = (reach - distance) / Scalar::from_f32(2.0);
+		self.pos.x -= normal.x * push;
+		self.pos.y -= normal.y * push;
+		other.pos.x += normal.x * push;
+		other.pos.y += normal.y * push;
+		let damage = (closing.to_f32() * RAM_DAMAGE).min(255.0) as u8;
+		self.health = self.health.saturating_sub(damage);
+		other.health = other.health.saturating_sub(damage);
+		Some(damage)
+	}
+
+	//first point on the way from start to end at the given distance from the centre,
+	//start itself if it is already that far out
+	fn dome_contact(start: &Vector, end: &Vector, radius: Scalar) -> Vector {
+		let zero = Scalar::default();
+		let d = Vector {
+			x: end.x - start.x,
+			y: end.y - start.y,
+		};
+		let length = d.mag();
+		let c = start.dot(start) - radius * radius;
+		if c >= zero || length == zero {
+			return *start;
+		}
+		//distance along the unit direction to the wall, written so that no intermediate
+		//gets much larger than radius^2, which would saturate in fixed point
+		let u = Vector {
+			x: d.x / length,
+			y: d.y / length,
+		};
+		let p = start.dot(&u);
+		let travel = ((p * p - c).sqrt() - p).min(length);
+		Vector {
+			x: start.x + u.x * travel,
+			y: start.y + u.y * travel,
+		}
+	}
+
 	#[cfg(not(target_arch = "wasm32"))]
 	pub fn advance_to_time(&mut self, time: u64) {
 		if time < self.time {
@@ -394,41 +468,47 @@ impl Trajectory {
 		hash: String,
 		update_time: u64,
 		time: u64,
-	) -> bool {
+	) -> Result<(), String> {
 		if update_time < self.time {
 			//happens when client requests a change in trajectory that happened before the current trajectory state on the server
-			eprintln!("Update is in the past! {} < {}", update_time, self.time);
-			return false;
+			return Err(format!(
+				"Update is in the past! {} < {}",
+				update_time, self.time
+			));
 		}
 		if update_time > time && (update_time - time) > MAX_TIME_AHEAD {
 			//client not allowed to advance server trajectory too much ahead.
-			eprintln!("Update is too far ahead!");
-			return false;
+			return Err("Update is too far ahead!".to_string());
 		}
 		if update_time < time && (time - update_time) > MAX_TIME_BEFORE {
-			eprintln!("Update is too long ago! diff = {}", time - update_time);
-			return false;
+			return Err(format!(
+				"Update is too long ago! diff = {}",
+				time - update_time
+			));
 		}
 		if !self.advance_to_time_check(update_time, hash) {
-			eprintln!("Hash mismatch!");
-			return false;
+			return Err("Hash mismatch!".to_string());
 		}
 		self.apply_change(change);
-		true
+		Ok(())
 	}
 
 	#[cfg(not(target_arch = "wasm32"))]
 	fn gen_spawn() -> Vector {
-		let normal = Normal::new(DOME_RADIUS / 4.0, DOME_RADIUS / 4.0).unwrap();
+		let map = current_map();
+		let time = current_time();
+		let dome_radius = map.dome_radius_at(time);
+		let normal = Normal::new(dome_radius.to_f32() / 4.0, dome_radius.to_f32() / 4.0).unwrap();
 		let mut pos: Vector;
 		loop {
 			pos = Vector {
-				x: normal.sample(&mut rand::thread_rng()),
-				y: normal.sample(&mut rand::thread_rng()),
+				x: Scalar::from_f32(normal.sample(&mut rand::thread_rng())),
+				y: Scalar::from_f32(normal.sample(&mut rand::thread_rng())),
 			};
-			let psum = Trajectory::pull_sum(&pos);
-			if psum.x.powf(2.0) + psum.y.powf(2.0) < SPAWN_PULL_MAX.powf(2.0)
-				&& pos.mag() < DOME_RADIUS
+			let psum = Trajectory::pull_at(&map, &map.bodies_at(time), &pos);
+			let spawn_pull_max = Scalar::from_f32(SPAWN_PULL_MAX);
+			if psum.x * psum.x + psum.y * psum.y < spawn_pull_max * spawn_pull_max
+				&& pos.mag() < dome_radius
 			{
 				break;
 			}
@@ -443,12 +523,13 @@ impl Default for Trajectory {
 		Trajectory {
 			propelling: false,
 			pos: Trajectory::gen_spawn(),
-			vel: Vector { x: 0.0, y: 0.0 },
+			vel: Vector::default(),
 			spin_direction: 0,
-			spin: 0.0,
+			spin: Scalar::default(),
 			boosters: 1,
 			time: current_time(),
 			health: 0xff,
+			fuel: MAX_FUEL,
 		}
 	}
 }
@@ -460,11 +541,11 @@ impl Trajectory {
 	pub fn from_b64(data: String) -> Trajectory {
 		let mut result: Trajectory =
 			deserialize(&general_purpose::STANDARD.decode(&data).unwrap()).unwrap();
-		result.updates = VecDeque::new();
+		result.timeline = Some(Box::new(Timeline::new(&result)));
 		result
 	}
 
-	//euler's method
+	//steps up to time with the map's integrator
 	//returns true if change occured
 	#[cfg(not(target_arch = "wasm32"))]
 	pub fn advance(&mut self, time: u64) -> bool {
@@ -483,61 +564,104 @@ impl Trajectory {
 		true
 	}
 
+	//steps up to time, applying the updates from other clients as it gets to them
+	//returns the changes as json, None if an update doesn't match and a correction is needed
 	#[cfg(target_arch = "wasm32")]
 	pub fn advance(&mut self, time: u64) -> Option<String> {
-		let mut changes: Vec<UpdateType> = Vec::new();
-		if time <= self.time {
-			return Some(serde_json::to_string(&changes).expect("Failed to serialize to JSON"));
+		match self.with_timeline(|timeline, current| timeline.advance(current, time)) {
+			Ok(changes) => {
+				Some(serde_json::to_string(&changes).expect("Failed to serialize to JSON"))
+			}
+			Err(error) => {
+				console_log!("{}", error);
+				None
+			}
 		}
-		let elapsed = (time - self.time) as u32;
-		let steps = elapsed / TIMESTEP_MILLIS;
-		for _ in 1..=steps {
-			loop {
-				if let Some(next_update) = self.updates.front().cloned() {
-					if next_update.time != self.time {
-						break;
-					}
-					if next_update.hash != self.hash_str() {
-						console_log!(
-							"Hash mismatch! request was {} but got {}",
-							next_update.hash,
-							self.hash_str()
-						);
-						return None;
-					}
-					changes.push(next_update.change.utype);
-					self.apply_change(next_update.change);
-					self.updates.pop_front();
-				} else {
-					break;
+	}
+
+	//takes the state the server sent, replaying the changes made since on top of it
+	#[cfg(target_arch = "wasm32")]
+	pub fn correct(&mut self, data: String) {
+		let authoritative = Trajectory::from_b64(data);
+		self.with_timeline(|timeline, current| timeline.correct(current, &authoritative));
+	}
+
+	//a change made by this client, kept so it survives corrections
+	#[cfg(target_arch = "wasm32")]
+	pub fn record_change(&mut self, change: UpdateTypeWrapper) {
+		self.with_timeline(|timeline, current| timeline.record(current, change));
+	}
+
+	//Blends between this state and the result of the real step after it, with a cubic curve
+	//that leaves and arrives with each state's velocity. Gravity, thrust and bounces are in the
+	//next state, so drawing at the times in between follows the path the ship really takes
+	//and lands exactly where the next step puts it. Times further ahead are stepped to first.
+	pub fn interpolate(&self, time: u64) -> State {
+		let at_rest = |trajectory: &Trajectory| State {
+			x: trajectory.pos.x.to_f32(),
+			y: trajectory.pos.y.to_f32(),
+			r: trajectory.spin.to_f32(),
+		};
+		let mut current = self.detached();
+		#[cfg(target_arch = "wasm32")]
+		let mut pending = self
+			.timeline
+			.iter()
+			.flat_map(|timeline| timeline.pending())
+			.peekable();
+		let step_millis = TIMESTEP_MILLIS as u64;
+		let next = loop {
+			if time <= current.time {
+				return self.smoothed(at_rest(&current), time);
+			}
+			let mut next = current.clone();
+			#[cfg(target_arch = "wasm32")]
+			while let Some(update) = pending.next_if(|update| update.time <= next.time) {
+				if update.time == next.time {
+					next.apply_change(update.change.clone()); //due before the next step
 				}
 			}
-			self.step();
-		}
-		Some(serde_json::to_string(&changes).expect("Failed to serialize to JSON"))
+			if !next.step() {
+				return self.smoothed(at_rest(&current), time); //dead
+			}
+			if next.time > time {
+				break next;
+			}
+			current = next;
+		};
+		let t = (time - current.time) as f32 / step_millis as f32;
+		let (t2, t3) = (t * t, t * t * t);
+		//hermite basis, the velocities are scaled to the length of a step
+		let (h00, h10, h01, h11) = (
+			2.0 * t3 - 3.0 * t2 + 1.0,
+			t3 - 2.0 * t2 + t,
+			-2.0 * t3 + 3.0 * t2,
+			t3 - t2,
+		);
+		let blend = |p0: Scalar, v0: Scalar, p1: Scalar, v1: Scalar| {
+			h00 * p0.to_f32()
+				+ h10 * v0.to_f32() * TIMESTEP_SECS
+				+ h01 * p1.to_f32()
+				+ h11 * v1.to_f32() * TIMESTEP_SECS
+		};
+		let drawn = State {
+			x: blend(current.pos.x, current.vel.x, next.pos.x, next.vel.x),
+			y: blend(current.pos.y, current.vel.y, next.pos.y, next.vel.y),
+			r: current.spin.to_f32() + (next.spin.to_f32() - current.spin.to_f32()) * t,
+		};
+		self.smoothed(drawn, time)
 	}
 
+	//the prediction as json, {"path": [x0, y0, ...], "crash": {"x", "y", "time"} or null}
 	#[cfg(target_arch = "wasm32")]
-	pub fn lerp(&self, time: u64) -> State {
-		if time < self.time {
-			return State {
-				x: self.pos.x,
-				y: self.pos.y,
-				r: self.spin,
-			};
-		}
-		let delta_millis = time - self.time;
-		let delta_secs = delta_millis as f32 / 1000f32;
-		State {
-			x: self.pos.x + self.vel.x * delta_secs,
-			y: self.pos.y + self.vel.y * delta_secs,
-			r: self.spin + (self.spin_direction as f32) * RADIANS_PER_SECOND * delta_secs,
-		}
+	#[wasm_bindgen(js_name = predict)]
+	pub fn predict_json(&self, steps: u32) -> String {
+		serde_json::to_string(&self.predict(steps)).expect("Failed to serialize to JSON")
 	}
 
 	pub fn dump(&self) -> String {
 		format!(
-			"{},{:x},{:x},{:x},{:x},{:x},{},{},{}",
+			"{},{:x},{:x},{:x},{:x},{:x},{},{},{},{}",
 			self.propelling,
 			self.pos.x.to_bits(),
 			self.pos.y.to_bits(),
@@ -546,73 +670,102 @@ impl Trajectory {
 			self.spin.to_bits(),
 			self.spin_direction,
 			self.time,
-			self.health
+			self.health,
+			self.fuel
 		)
 	}
 
+	//an update from another client, a late one rewinds and replays the ship
+	//false if it is too old or doesn't match, a correction is needed then
 	#[cfg(target_arch = "wasm32")]
 	pub fn insert_update(&mut self, change: UpdateTypeWrapper, hash: String, time: u64) -> bool {
-		//true if not in the past
-		if self.time > time {
-			return false;
+		let input = Input {
+			time,
+			hash: Some(hash),
+			change,
+		};
+		let inserted = self.with_timeline(|timeline, current| timeline.insert(current, input));
+		if let Err(error) = &inserted {
+			console_log!("{}", error);
 		}
-		self.updates.push_back(TrajectoryUpdate {
-			time: time,
-			hash: hash,
-			change: change,
-		});
-		true
+		inserted.is_ok()
 	}
 
-	//TODO consider when velocity exceeds radius, use line_intersects_circle?
-	fn collides(&self) -> bool {
-		for body in BODIES {
-			if body.collides(&self.pos) {
-				return true;
-			}
+	//Sweeps the player's circle from its previous position to the current one, so a fast
+	//ship can't jump over a body between two steps. Works relative to each body so that
+	//orbiting bodies, which move during the step as well, are swept too.
+	fn collides(&self, map: &Map, prev_pos: &Vector, before: &[Body], after: &[Body]) -> bool {
+		let sweeps = |index: usize| {
+			let (old, new) = (&before[index], &after[index]);
+			let start = Vector {
+				x: prev_pos.x - old.pos.x,
+				y: prev_pos.y - old.pos.y,
+			};
+			let end = Vector {
+				x: self.pos.x - new.pos.x,
+				y: self.pos.y - new.pos.y,
+			};
+			new.sweep_collides(&start, &end)
+		};
+		//only bodies near the end of the step can be touched, unless the step was very long
+		let travelled = Vector {
+			x: self.pos.x - prev_pos.x,
+			y: self.pos.y - prev_pos.y,
+		}
+		.mag();
+		let grid = map.gravity();
+		match grid.near_bodies(
+			after,
+			&self.pos,
+			travelled + Scalar::from_f32(PLAYER_RADIUS),
+		) {
+			Some(near) => near.iter().chain(grid.moving()).any(|&index| sweeps(index)),
+			None => (0..after.len()).any(sweeps),
 		}
-		return false;
 	}
 
 	pub fn hits(&self, other: &Trajectory) -> f32 {
 		//shift everything to make line start from origin
 		let a = other.pos.x - self.pos.x;
 		let b = other.pos.y - self.pos.y;
-		let rot_90 = self.spin - HALFPI;
+		let rot_90 = self.spin - Scalar::from_f32(HALFPI);
+		let two = Scalar::from_f32(2.0);
+		let zero = Scalar::default();
+		let radius = Scalar::from_f32(PLAYER_RADIUS);
+		let reach = Scalar::from_f32(PISTOL_REACH);
 
 		//compute the quadratic's 'b' coefficient (for variable r in polar form)
-		let qb = -(2f32 * a * rot_90.cos() + 2f32 * b * rot_90.sin());
-		let discriminant: f32 = qb.powi(2) - 4f32 * (a.powi(2) + b.powi(2) - PLAYER_RADIUS.powi(2));
-		if discriminant < 0f32 {
+		let qb = -(two * a * rot_90.cos_det() + two * b * rot_90.sin_det());
+		let discriminant = qb * qb - Scalar::from_f32(4.0) * (a * a + b * b - radius * radius);
+		if discriminant < zero {
 			//no real roots (no line-circle intersection)
 			return -1f32;
 		}
 
 		let root = discriminant.sqrt();
 
-		let r1 = (root - qb) / 2f32;
-		let r2 = (-root - qb) / 2f32;
+		let r1 = (root - qb) / two;
+		let r2 = (-root - qb) / two;
 
-		let r1_good = PISTOL_REACH > r1 && r1 > 0f32;
-		let r2_good = PISTOL_REACH > r2 && r2 > 0f32;
+		let r1_good = reach > r1 && r1 > zero;
+		let r2_good = reach > r2 && r2 > zero;
 
 		if !r1_good && !r2_good {
 			-1f32
 		} else if r1_good != r2_good {
 			if r1_good {
-				r1
+				r1.to_f32()
 			} else {
-				r2
+				r2.to_f32()
 			}
 		} else {
-			f32::min(r1, r2)
+			r1.min(r2).to_f32()
 		}
 	}
 
+	//xxh3 of the canonical layout below, so it is the same on every platform and rust release
 	pub fn hash_str(&self) -> String {
-		let mut hasher = DefaultHasher::new();
-		self.hash(&mut hasher);
-		format!("{:x}", hasher.finish())
+		format!("{:x}", xxh3_64(&self.hash_bytes()))
 	}
 
 	pub fn apply_change(&mut self, change: UpdateTypeWrapper) {
@@ -627,7 +780,7 @@ impl Trajectory {
 				self.spin_direction = -1;
 			}
 			UpdateType::PropOn => {
-				self.propelling = true;
+				self.propelling = self.fuel > 0;
 			}
 			UpdateType::PropOff => {
 				self.propelling = false;
@@ -641,32 +794,115 @@ impl Trajectory {
 			UpdateType::Health => {
 				self.health = self.health.saturating_add(change.value.unwrap_or(0));
 			}
+			UpdateType::Refuel => {
+				let amount = change
+					.value
+					.map_or(MAX_FUEL, |percent| percent.min(100) as u16 * MAX_FUEL / 100);
+				self.fuel = self.fuel.saturating_add(amount).min(MAX_FUEL);
+			}
 		}
 	}
 }
 
-impl Hash for Trajectory {
-	fn hash<H: Hasher>(&self, state: &mut H) {
-		self.propelling.hash(state);
-		self.pos.hash(state);
-		self.vel.hash(state);
-		self.spin.to_bits().hash(state); // Manually hash f32 field
-		self.spin_direction.hash(state);
-		self.time.hash(state);
-		self.health.hash(state);
+impl Trajectory {
+	//the state alone, without what a client keeps for reconciling it
+	pub(crate) fn detached(&self) -> Trajectory {
+		Trajectory {
+			propelling: self.propelling,
+			pos: self.pos,
+			vel: self.vel,
+			spin: self.spin,
+			spin_direction: self.spin_direction,
+			time: self.time,
+			health: self.health,
+			boosters: self.boosters,
+			fuel: self.fuel,
+			#[cfg(target_arch = "wasm32")]
+			timeline: None,
+		}
+	}
+
+	#[cfg(target_arch = "wasm32")]
+	fn with_timeline<T>(&mut self, f: impl FnOnce(&mut Timeline, &mut Trajectory) -> T) -> T {
+		let mut timeline = self
+			.timeline
+			.take()
+			.unwrap_or_else(|| Box::new(Timeline::new(self)));
+		let result = f(&mut timeline, self);
+		self.timeline = Some(timeline);
+		result
+	}
+
+	//where to draw the ship, easing out the last jump the timeline made
+	#[cfg(target_arch = "wasm32")]
+	fn smoothed(&self, drawn: State, time: u64) -> State {
+		match &self.timeline {
+			Some(timeline) => timeline.smooth(drawn, time),
+			None => drawn,
+		}
+	}
+
+	#[cfg(not(target_arch = "wasm32"))]
+	fn smoothed(&self, drawn: State, _time: u64) -> State {
+		drawn
+	}
+
+	//Every field step depends on, in declaration order and little endian. Scalars are
+	//their raw bits widened to 8 bytes. Adding a field here means bumping HASH_VERSION.
+	fn hash_bytes(&self) -> Vec<u8> {
+		let mut bytes = Vec::with_capacity(48);
+		bytes.push(self.propelling as u8);
+		for value in [self.pos.x, self.pos.y, self.vel.x, self.vel.y, self.spin] {
+			bytes.extend_from_slice(&value.hash_bits().to_le_bytes());
+		}
+		bytes.extend_from_slice(&self.spin_direction.to_le_bytes());
+		bytes.extend_from_slice(&self.time.to_le_bytes());
+		bytes.push(self.health);
+		bytes.push(self.boosters);
+		bytes.extend_from_slice(&self.fuel.to_le_bytes());
+		bytes
+	}
+
+	//A projectile leaving the nose of the ship at speed relative to it. It is stepped like a
+	//ship with its engine off, with enough health to outlive the dome damage of a long flight.
+	#[cfg(not(target_arch = "wasm32"))]
+	pub fn launched(&self, speed: Scalar) -> Trajectory {
+		let facing = self.spin - Scalar::from_f32(HALFPI);
+		let direction = Vector {
+			x: facing.cos_det(),
+			y: facing.sin_det(),
+		};
+		let offset = Scalar::from_f32(PLAYER_RADIUS);
+		Trajectory {
+			propelling: false,
+			pos: Vector {
+				x: self.pos.x + direction.x * offset,
+				y: self.pos.y + direction.y * offset,
+			},
+			vel: Vector {
+				x: self.vel.x + direction.x * speed,
+				y: self.vel.y + direction.y * speed,
+			},
+			spin: self.spin,
+			spin_direction: 0,
+			time: self.time,
+			health: u8::MAX,
+			boosters: 0,
+			fuel: 0,
+		}
 	}
 }
 
-#[derive(Serialize, Debug, Clone, Copy, Deserialize)]
+#[derive(Serialize, Debug, Clone, Copy, Deserialize, Default)]
 #[wasm_bindgen]
 pub struct Vector {
-	pub x: f32,
-	pub y: f32,
+	pub x: Scalar,
+	pub y: Scalar,
 }
 
 impl Vector {
-	pub fn mag(&self) -> f32 {
-		(self.x.powi(2) + self.y.powi(2)).sqrt()
+	pub fn mag(&self) -> Scalar {
+		(self.x * self.x + self.y * self.y).sqrt()
 	}
 
 	pub fn normalized(&self) -> Vector {
@@ -680,37 +916,42 @@ impl Vector {
 	pub fn reflect(&mut self, n: &Vector) {
 		let normalized = n.normalized();
 		let dot_product = self.dot(&normalized);
-		self.x -= 2.0 * dot_product * normalized.x;
-		self.y -= 2.0 * dot_product * normalized.y;
+		let two = Scalar::from_f32(2.0);
+		self.x -= two * dot_product * normalized.x;
+		self.y -= two * dot_product * normalized.y;
 	}
 
-	pub fn dot(&self, v: &Vector) -> f32 {
+	pub fn dot(&self, v: &Vector) -> Scalar {
 		self.x * v.x + self.y * v.y
 	}
 }
 
-impl Hash for Vector {
-	fn hash<H: Hasher>(&self, state: &mut H) {
-		self.x.to_bits().hash(state);
-		self.y.to_bits().hash(state);
-	}
-}
-
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 #[wasm_bindgen]
 pub struct Body {
-	pub pos: Vector,
-	pub radius: f32,
+	#[serde(default)]
+	pub pos: Vector, //for orbiting bodies without a parent this is the centre of the orbit
+	pub radius: Scalar,
+
+	#[serde(default)]
+	#[wasm_bindgen(skip)]
+	pub orbit: Option<Orbit>,
+
+	//Relative to pos, ships crash into this shape instead of the circle of radius. The radius
+	//still sets the mass, gravity doesn't follow the outline.
+	#[serde(default)]
+	#[wasm_bindgen(skip)]
+	pub outline: Option<BeizerLoop>,
 }
 
 #[wasm_bindgen]
 impl Body {
 	//returns the acceleration imposed by itself at a point
-	fn pull(&self, pos: &Vector) -> Vector {
+	pub(crate) fn pull(&self, pos: &Vector) -> Vector {
 		let xdiff = self.pos.x - pos.x;
 		let ydiff = self.pos.y - pos.y;
-		let powsum = xdiff.powi(2) + ydiff.powi(2);
-		let mag = G * self.mass() / powsum;
+		let powsum = xdiff * xdiff + ydiff * ydiff;
+		let mag = Scalar::from_f32(G) * self.mass() / powsum;
 		let dist = powsum.sqrt();
 		Vector {
 			x: mag * xdiff / dist,
@@ -718,53 +959,258 @@ impl Body {
 		}
 	}
 
-	fn collides(&self, pos: &Vector) -> bool {
-		(self.radius + PLAYER_RADIUS).powi(2)
-			> (pos.x - self.pos.x).powi(2) + (pos.y - self.pos.y).powi(2)
+	//how far from the centre a ship can crash into the body
+	pub(crate) fn extent(&self) -> Scalar {
+		match &self.outline {
+			Some(outline) => outline.extent(),
+			None => self.radius,
+		}
 	}
 
-	fn mass(&self) -> f32 {
-		self.radius * self.radius * PI
+	//start and end are relative to the centre of the body
+	fn sweep_collides(&self, start: &Vector, end: &Vector) -> bool {
+		let player_radius = Scalar::from_f32(PLAYER_RADIUS);
+		let reach = self.extent() + player_radius;
+		let d = Vector {
+			x: end.x - start.x,
+			y: end.y - start.y,
+		};
+		let length_sq = d.dot(&d);
+		//point on the segment closest to the centre
+		let t = if length_sq == Scalar::default() {
+			Scalar::default()
+		} else {
+			(-start.dot(&d) / length_sq)
+				.max(Scalar::default())
+				.min(Scalar::from_f32(1.0))
+		};
+		let closest = Vector {
+			x: start.x + d.x * t,
+			y: start.y + d.y * t,
+		};
+		if reach * reach <= closest.dot(&closest) {
+			return false;
+		}
+		match &self.outline {
+			Some(outline) => outline.sweep_collides(start, end, player_radius),
+			None => true,
+		}
+	}
+
+	fn mass(&self) -> Scalar {
+		self.radius * self.radius * Scalar::from_f32(PI)
 	}
 }
 
+const OUTLINE_SEGMENTS: u32 = 8; //straight pieces each curve of an outline is tested as
+
 pub struct QuadBeizer {
 	s: Vector, //start
 	c: Vector, //control
 	e: Vector, //end
 }
 
-#[derive(Clone)]
+impl QuadBeizer {
+	pub fn point_at(&self, t: Scalar) -> Vector {
+		let u = Scalar::from_f32(1.0) - t;
+		let two = Scalar::from_f32(2.0);
+		Vector {
+			x: u * u * self.s.x + two * u * t * self.c.x + t * t * self.e.x,
+			y: u * u * self.s.y + two * u * t * self.c.y + t * t * self.e.y,
+		}
+	}
+}
+
+//a point of the outline and the control point of the curve from it to the next part's point
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
 pub struct LoopPart {
 	s: Vector,
 	c: Vector,
 }
 
+//A closed outline made of quadratic curves, the last one ends where the first starts. Written
+//in maps as the list of its parts.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(try_from = "Vec<LoopPart>", into = "Vec<LoopPart>")]
 pub struct BeizerLoop {
 	parts: Vec<LoopPart>,
+	polygon: Vec<Vector>, //flattened once, ships sweep against it every step near the body
 }
 
 impl BeizerLoop {
 	pub fn new(parts: Vec<LoopPart>) -> Result<Self, String> {
 		if parts.len() < 2 {
-			return Err(format!("Beizer loop instantiated without closure"));
+			return Err("Beizer loop instantiated without closure".to_string());
 		}
-		Ok(BeizerLoop { parts: parts })
+		let mut outline = BeizerLoop {
+			parts,
+			polygon: Vec::new(),
+		};
+		outline.polygon = outline.flatten();
+		Ok(outline)
 	}
 
 	pub fn num_beizers(&self) -> u32 {
-		(self.parts.len() as u32) * 2 / 3
+		self.parts.len() as u32
 	}
 
 	pub fn get_curve(&self, index: i32) -> QuadBeizer {
-		let prev = self.parts[index.rem_euclid(self.parts.len() as i32) as usize].clone();
-		let next = self.parts[(index + 1).rem_euclid(self.parts.len() as i32) as usize].clone();
+		let prev = self.parts[index.rem_euclid(self.parts.len() as i32) as usize];
+		let next = self.parts[(index + 1).rem_euclid(self.parts.len() as i32) as usize];
 		QuadBeizer {
 			s: prev.s,
 			c: prev.c,
 			e: next.s,
 		}
 	}
+
+	//a curve never leaves the hull of its points, so none is further out than the furthest point
+	pub fn extent(&self) -> Scalar {
+		self.parts
+			.iter()
+			.flat_map(|part| [part.s.mag(), part.c.mag()])
+			.fold(Scalar::default(), |a, b| a.max(b))
+	}
+
+	//the outline as a closed polygon, OUTLINE_SEGMENTS corners per curve
+	pub fn polygon(&self) -> &[Vector] {
+		&self.polygon
+	}
+
+	fn flatten(&self) -> Vec<Vector> {
+		let steps = Scalar::from_f32(OUTLINE_SEGMENTS as f32);
+		(0..self.num_beizers() as i32)
+			.flat_map(|index| {
+				let curve = self.get_curve(index);
+				(0..OUTLINE_SEGMENTS)
+					.map(move |step| curve.point_at(Scalar::from_f32(step as f32) / steps))
+			})
+			.collect()
+	}
+
+	//Whether a circle of radius moving from start to end touches the outline or ends up inside
+	//it, everything relative to the centre. The same on every platform as the curves are
+	//flattened the same way.
+	pub fn sweep_collides(&self, start: &Vector, end: &Vector, radius: Scalar) -> bool {
+		let polygon = &self.polygon;
+		let corners = polygon.len();
+		let touches = (0..corners).any(|index| {
+			let (a, b) = (&polygon[index], &polygon[(index + 1) % corners]);
+			segments_distance_sq(start, end, a, b) < radius * radius
+		});
+		touches || polygon_contains(polygon, end)
+	}
+
+	//[s0.x, s0.y, c0.x, c0.y, s1.x, ...], for drawing with moveTo(s0) and then
+	//quadraticCurveTo(c, next s) for every part, wrapping around to s0
+	pub fn to_flat(&self) -> Vec<f32> {
+		self.parts
+			.iter()
+			.flat_map(|part| {
+				[
+					part.s.x.to_f32(),
+					part.s.y.to_f32(),
+					part.c.x.to_f32(),
+					part.c.y.to_f32(),
+				]
+			})
+			.collect()
+	}
+}
+
+impl TryFrom<Vec<LoopPart>> for BeizerLoop {
+	type Error = String;
+
+	fn try_from(parts: Vec<LoopPart>) -> Result<Self, String> {
+		BeizerLoop::new(parts)
+	}
+}
+
+impl From<BeizerLoop> for Vec<LoopPart> {
+	fn from(outline: BeizerLoop) -> Vec<LoopPart> {
+		outline.parts
+	}
+}
+
+fn closest_on_segment(p: &Vector, a: &Vector, b: &Vector) -> Vector {
+	let d = Vector {
+		x: b.x - a.x,
+		y: b.y - a.y,
+	};
+	let length_sq = d.dot(&d);
+	let t = if length_sq == Scalar::default() {
+		Scalar::default()
+	} else {
+		let ap = Vector {
+			x: p.x - a.x,
+			y: p.y - a.y,
+		};
+		(ap.dot(&d) / length_sq)
+			.max(Scalar::default())
+			.min(Scalar::from_f32(1.0))
+	};
+	Vector {
+		x: a.x + d.x * t,
+		y: a.y + d.y * t,
+	}
+}
+
+//which side of the line through a and b the point p is on, 0 if on it
+fn side(a: &Vector, b: &Vector, p: &Vector) -> Scalar {
+	(b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+}
+
+fn segments_distance_sq(a: &Vector, b: &Vector, c: &Vector, d: &Vector) -> Scalar {
+	let zero = Scalar::default();
+	let crosses = |first: Scalar, second: Scalar| {
+		(first < zero && second > zero) || (first > zero && second < zero)
+	};
+	if crosses(side(a, b, c), side(a, b, d)) && crosses(side(c, d, a), side(c, d, b)) {
+		return zero;
+	}
+	[(a, c, d), (b, c, d), (c, a, b), (d, a, b)]
+		.iter()
+		.map(|(p, from, to)| {
+			let closest = closest_on_segment(p, from, to);
+			let gap = Vector {
+				x: p.x - closest.x,
+				y: p.y - closest.y,
+			};
+			gap.dot(&gap)
+		})
+		.fold(Scalar::MAX, |a, b| a.min(b))
+}
+
+//even-odd rule, counting the edges a ray from p towards +x crosses
+fn polygon_contains(polygon: &[Vector], p: &Vector) -> bool {
+	let mut inside = false;
+	for index in 0..polygon.len() {
+		let (a, b) = (&polygon[index], &polygon[(index + 1) % polygon.len()]);
+		if (a.y > p.y) != (b.y > p.y) {
+			let x = a.x + (p.y - a.y) * (b.x - a.x) / (b.y - a.y);
+			if p.x < x {
+				inside = !inside;
+			}
+		}
+	}
+	inside
+}
+
+//sent by the client when connecting, the numeric backend and the map's integrator are
+//included since they change every hash
+#[wasm_bindgen]
+pub fn hash_version() -> String {
+	format!(
+		"{}-{}-{}",
+		HASH_VERSION,
+		BACKEND,
+		current_map().integrator.name()
+	)
+}
+
+#[wasm_bindgen]
+pub fn max_fuel() -> u16 {
+	MAX_FUEL
 }
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -774,28 +1220,51 @@ pub fn current_time() -> u64 {
 	current_time.as_millis() as u64
 }
 
-fn normalize_angle(x: f32) -> f32 {
-	let modded = ((x % TWOPI) + TWOPI) % TWOPI;
-	PI - modded
+#[wasm_bindgen]
+#[cfg(target_arch = "wasm32")]
+pub fn getbody(index: usize) -> Option<Body> {
+	current_map().bodies.get(index).cloned()
 }
 
+//positions of all bodies at the given time, flattened as [x0, y0, x1, y1, ...]
 #[wasm_bindgen]
 #[cfg(target_arch = "wasm32")]
-pub fn getbody(index: usize) -> Option<Body> {
-	if index >= BODIES.len() {
-		return None;
-	}
-	Some(BODIES[index].clone())
+pub fn body_positions(time: u64) -> Vec<f32> {
+	current_map()
+		.bodies_at(time)
+		.iter()
+		.flat_map(|body| [body.pos.x.to_f32(), body.pos.y.to_f32()])
+		.collect()
+}
+
+//outline of a body relative to its position as BeizerLoop::to_flat gives it, empty for circles
+#[wasm_bindgen]
+#[cfg(target_arch = "wasm32")]
+pub fn body_outline(index: usize) -> Vec<f32> {
+	current_map()
+		.bodies
+		.get(index)
+		.and_then(|body| body.outline.as_ref())
+		.map(|outline| outline.to_flat())
+		.unwrap_or_default()
 }
 
 #[wasm_bindgen]
 #[cfg(target_arch = "wasm32")]
 pub fn num_bodies() -> usize {
-	BODIES.len()
+	current_map().bodies.len()
+}
+
+//radius of the dome at the given time, it may shrink during a round
+#[wasm_bindgen]
+#[cfg(target_arch = "wasm32")]
+pub fn dome_radius(time: u64) -> f32 {
+	current_map().dome_radius_at(time).to_f32()
 }
 
+//the radius the dome has at its largest
 #[wasm_bindgen]
 #[cfg(target_arch = "wasm32")]
-pub fn dome_radius() -> f32 {
-	DOME_RADIUS
+pub fn max_dome_radius() -> f32 {
+	current_map().dome_radius.to_f32()
 }
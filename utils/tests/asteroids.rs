@@ -0,0 +1,74 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+//a test binary of its own since it sets a different map than the other tests
+
+use utils::map::{current_map, set_map, Map};
+use utils::scalar::{Real, Scalar};
+use utils::trajectory::{Trajectory, Vector, MAX_FUEL};
+
+fn load_asteroids() {
+	set_map(Map::from_toml(include_str!("../../maps/asteroids.toml")).unwrap());
+}
+
+fn vector(x: f32, y: f32) -> Vector {
+	Vector {
+		x: Scalar::from_f32(x),
+		y: Scalar::from_f32(y),
+	}
+}
+
+#[test]
+fn outlines_shape_crashes() {
+	load_asteroids();
+	let map = current_map();
+	let shard = map.bodies[1].outline.as_ref().unwrap();
+	assert_eq!(shard.num_beizers(), 3);
+	assert!((shard.extent().to_f32() - 300.17).abs() < 0.1);
+
+	let radius = Scalar::from_f32(25.0);
+	let resting = |x: f32, y: f32| shard.sweep_collides(&vector(x, y), &vector(x, y), radius);
+	//the lowest point of the bottom curve is about 45 below the centre
+	assert!(!resting(15.0, -80.0));
+	assert!(resting(15.0, -60.0));
+	assert!(resting(0.0, 0.0));
+	//far out along the tip, where the circle of the radius doesn't reach
+	assert!(resting(230.0, 0.0));
+	assert!(!resting(230.0, 120.0));
+	//fast enough to go through in a single step
+	assert!(shard.sweep_collides(&vector(0.0, -300.0), &vector(0.0, 300.0), radius));
+
+	let mut ship = Trajectory {
+		pos: vector(1200.0 + 230.0, -600.0),
+		time: 0,
+		health: 100,
+		boosters: 1,
+		fuel: MAX_FUEL,
+		..Default::default()
+	};
+	ship.step();
+	assert_eq!(ship.health, 0);
+}
+
+#[test]
+fn outlines_are_part_of_the_map() {
+	let data = include_str!("../../maps/asteroids.toml");
+	let original = Map::from_toml(data).unwrap();
+	let reshaped = Map::from_toml(&data.replace(
+		"{ s = { x = 260.0, y = 40.0 }",
+		"{ s = { x = 261.0, y = 40.0 }",
+	))
+	.unwrap();
+	assert_ne!(original.hash_str(), reshaped.hash_str());
+	let roundtrip = Map::from_json(&original.to_json()).unwrap();
+	assert_eq!(original.hash_str(), roundtrip.hash_str());
+
+	let open = data.replace(
+		"outline = [\n\t{ s = { x = 50.0, y = 0.0 }, c = { x = 40.0, y = 45.0 } },\n",
+		"outline = [\n",
+	);
+	let open = open.replace(
+		"\t{ s = { x = -30.0, y = 35.0 }, c = { x = -60.0, y = 0.0 } },\n",
+		"",
+	);
+	assert!(Map::from_toml(&open).is_err());
+}
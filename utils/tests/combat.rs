@@ -0,0 +1,206 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+mod common;
+
+use common::{load_default_map, ship};
+use utils::bullet::{Blaster, Bullet, Impact};
+use utils::grenade::{Blast, Grenade, MAX_PRESS_TIME};
+use utils::history::TrajectoryHistory;
+use utils::messages::{ShootInfo, Victim};
+use utils::scalar::{Real, Scalar};
+use utils::server_gameobjects::validate_hit;
+use utils::trajectory::{Trajectory, Vector};
+
+#[test]
+fn server_validates_hits() {
+	load_default_map();
+	let facing = |x: f32, spin: f32| Trajectory {
+		spin: Scalar::from_f32(spin),
+		time: 1000,
+		..ship(x, -2500.0)
+	};
+	//facing the target 300 to the right
+	let shooter = facing(-300.0, std::f32::consts::FRAC_PI_2);
+	let target = facing(0.0, 0.0);
+	let mut shooter_history = TrajectoryHistory::new(&shooter);
+	let mut target_history = TrajectoryHistory::new(&target);
+	let (mut shooter_now, mut target_now) = (shooter.clone(), target.clone());
+	for _ in 0..20 {
+		shooter_now.step();
+		target_now.step();
+		shooter_history.checkpoint(&shooter_now);
+		target_history.checkpoint(&target_now);
+	}
+	let shot = ShootInfo {
+		at: shooter.hash_str(),
+		stime: shooter.time,
+		shooter: None,
+		victim: None,
+	};
+	let victim = Victim {
+		id: String::new(),
+		hash: target.hash_str(),
+		time: target.time,
+		loot: None,
+	};
+	let check = |shot: &ShootInfo, victim: &Victim, now: u64| {
+		validate_hit(&shooter_history, shot, victim, &target_history, now)
+	};
+	let distance = check(&shot, &victim, 1200).unwrap();
+	assert!((270.0..280.0).contains(&distance));
+
+	assert!(check(&shot, &victim, 1000 + 2001).is_err());
+	let lying = ShootInfo {
+		at: target.hash_str(),
+		..shot.clone()
+	};
+	assert_eq!(
+		check(&lying, &victim, 1200).unwrap_err(),
+		"Shooter hash mismatch"
+	);
+	let future = Victim {
+		time: 1100,
+		..victim.clone()
+	};
+	assert!(check(&shot, &future, 1200).is_err());
+
+	//turned away, the same shot misses
+	let turned = facing(-300.0, -std::f32::consts::FRAC_PI_2);
+	let missed = ShootInfo {
+		at: turned.hash_str(),
+		..shot.clone()
+	};
+	assert_eq!(
+		validate_hit(
+			&TrajectoryHistory::new(&turned),
+			&missed,
+			&victim,
+			&target_history,
+			1200
+		)
+		.unwrap_err(),
+		"Missed"
+	);
+}
+
+#[test]
+fn grenades_fly_and_go_off() {
+	load_default_map();
+	let thrower = |x: f32, y: f32| Trajectory {
+		time: 1000,
+		..ship(x, y)
+	};
+	let settings = Blast::default();
+	//facing -y, nothing is in the way for the whole fuse
+	let ship = thrower(-1000.0, -2000.0);
+	let weak = Grenade::throw("a".to_string(), &ship, 0, &settings);
+	let strong = Grenade::throw("a".to_string(), &ship, MAX_PRESS_TIME * 2, &settings);
+	assert!(weak.flight.vel.y.to_f32() < 0.0);
+	assert!(strong.flight.vel.mag().to_f32() > 4.0 * weak.flight.vel.mag().to_f32());
+	assert_eq!(
+		strong.flight.vel.mag().to_bits(),
+		Grenade::throw("a".to_string(), &ship, MAX_PRESS_TIME, &settings)
+			.flight
+			.vel
+			.mag()
+			.to_bits()
+	);
+
+	let mut fused = weak.clone();
+	assert!(fused.advance(ship.time + settings.fuse / 2).is_none());
+	let blast = fused.advance(ship.time + settings.fuse * 2).unwrap();
+	assert_eq!(fused.flight.time, ship.time + settings.fuse);
+	assert_eq!(blast.x.to_bits(), fused.flight.pos.x.to_bits());
+
+	//thrown at the body right below
+	let mut crashing = Grenade::throw(
+		"a".to_string(),
+		&thrower(1123.0, -4000.0),
+		MAX_PRESS_TIME,
+		&settings,
+	);
+	assert!(crashing.advance(ship.time + settings.fuse).is_some());
+	assert!(crashing.flight.time < crashing.fuse_end);
+
+	let at = |distance: f32| Trajectory {
+		pos: Vector {
+			x: fused.flight.pos.x + Scalar::from_f32(distance),
+			y: fused.flight.pos.y,
+		},
+		..ship.clone()
+	};
+	assert_eq!(fused.damage_to(&at(0.0)), 80);
+	let (near, far) = (fused.damage_to(&at(100.0)), fused.damage_to(&at(200.0)));
+	assert!(80 > near && near > far && far > 0);
+	assert_eq!(fused.damage_to(&at(settings.radius + 30.0)), 0);
+
+	//the settings go with the grenade
+	let small = Blast {
+		radius: 50.0,
+		damage: 10.0,
+		..settings
+	};
+	let mut weaker = Grenade::throw("a".to_string(), &ship, 0, &small);
+	weaker.advance(ship.time + settings.fuse * 2);
+	assert_eq!(weaker.damage_to(&at(0.0)), 10);
+	assert_eq!(weaker.damage_to(&at(100.0)), 0);
+}
+
+#[test]
+fn bullets_travel_and_hit() {
+	load_default_map();
+	let flying = |x: f32, y: f32, spin: f32| Trajectory {
+		vel: Vector {
+			x: Scalar::from_f32(100.0),
+			y: Scalar::default(),
+		},
+		spin: Scalar::from_f32(spin),
+		time: 1000,
+		..ship(x, y)
+	};
+	let blaster = Blaster::default();
+	//facing -y
+	let shooter = flying(-1000.0, -2000.0, 0.0);
+	let bullet = Bullet::fire("a".to_string(), &shooter, &blaster);
+	assert!((bullet.flight.vel.x.to_f32() - shooter.vel.x.to_f32()).abs() < 1.0);
+	assert!((bullet.flight.vel.y.to_f32() + blaster.muzzle_speed).abs() < 1.0);
+	assert_eq!(bullet.damage, blaster.damage);
+
+	//takes a few steps to get there, moving along with the shooter
+	let mut hitting = bullet.clone();
+	let mut ships = [flying(-1000.0, -2400.0, 0.0), flying(-900.0, -2400.0, 0.0)];
+	assert!(hitting.advance(shooter.time + 200, &mut ships).is_none());
+	assert_eq!(
+		hitting.advance(shooter.time + 2000, &mut ships),
+		Some(Impact::Ship(0))
+	);
+	assert!(hitting.flight.time > shooter.time + 300);
+	assert_eq!(ships[0].time, hitting.flight.time);
+
+	//dead ships can't be hit
+	let mut dead = flying(-1000.0, -2400.0, 0.0);
+	dead.health = 0;
+	let mut passing = bullet.clone();
+	assert_ne!(
+		passing.advance(shooter.time + 2000, &mut [dead]),
+		Some(Impact::Ship(0))
+	);
+
+	//fired at the body right below
+	let mut absorbed = Bullet::fire("a".to_string(), &flying(1123.0, -4000.0, 0.0), &blaster);
+	assert_eq!(
+		absorbed.advance(shooter.time + blaster.lifetime, &mut []),
+		Some(Impact::Body)
+	);
+
+	let mut fading = Bullet::fire(
+		"a".to_string(),
+		&flying(-1000.0, -2000.0, std::f32::consts::PI),
+		&blaster,
+	);
+	assert_eq!(
+		fading.advance(shooter.time + 2 * blaster.lifetime, &mut []),
+		Some(Impact::Expired)
+	);
+	assert_eq!(fading.flight.time, fading.expires);
+}
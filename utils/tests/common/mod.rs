@@ -0,0 +1,25 @@
+//what the test binaries share, each uses only some of it
+#![allow(dead_code)]
+
+use utils::map::{set_map, Map};
+use utils::scalar::{Real, Scalar};
+use utils::trajectory::{Trajectory, Vector, MAX_FUEL};
+
+pub fn load_default_map() {
+	set_map(Map::from_toml(include_str!("../../../maps/default.toml")).unwrap());
+}
+
+//a healthy ship at rest with a full tank, tests set whatever else matters to them
+pub fn ship(x: f32, y: f32) -> Trajectory {
+	Trajectory {
+		pos: Vector {
+			x: Scalar::from_f32(x),
+			y: Scalar::from_f32(y),
+		},
+		time: 0,
+		health: 100,
+		boosters: 1,
+		fuel: MAX_FUEL,
+		..Default::default()
+	}
+}
@@ -0,0 +1,204 @@
+//Ships on the default map as base64 bincode, with the hash each has after STEPS steps. Both the
+//native and the wasm tests step them, so the physics can't drift between the two or away from
+//what the server checks. Regenerate the hashes whenever step changes, together with HASH_VERSION.
+
+pub const STEPS: u32 = 10000000;
+
+pub const PINNED: [(&str, &str); 49] = [
+	(
+		"APuBgkQuMbJEyL+YwRhjcsCobCtB/6cQJhOKAQAA/wFYAg==",
+		"746c2aef6b1aca51",
+	),
+	(
+		"AapOIUX+kapEfGxawXiWjEGTgqTCAf4QJhOKAQAA/wFYAg==",
+		"fb193415a40184e",
+	),
+	(
+		"AcQkcMQmmERFjFJfwTyjnkGoC8XBAFURJhOKAQAA/wFYAg==",
+		"5965df20ff1003b0",
+	),
+	(
+		"AO69isRGhwdFgOcTQMAoTUGk1DtCAewcJhOKAQAA/wFYAg==",
+		"7493c52024056d10",
+	),
+	(
+		"ABmaD0XcT5REfx6ZwXiMDMFQcG/C/0QdJhOKAQAA/wFYAg==",
+		"5e9182ba978c2fb8",
+	),
+	(
+		"ACquYkWkgTVEjGl2QXwntkC8mq1CAZsdJhOKAQAA/wFYAg==",
+		"def7416521baeb1",
+	),
+	(
+		"AfY4OEVM0E5FGOufQKhTkEB4RNpBAfMdJhOKAQAA/wFYAg==",
+		"d69a3e47b311159f",
+	),
+	(
+		"AeCJKEWrlIhFC80uwfDku7+Au/o/AUoeJhOKAQAA/wFYAg==",
+		"273f63724c5308d2",
+	),
+	(
+		"AemMkkTnIAREAAm4P0oAP0EG1KnC/5seJhOKAQAA/wFYAg==",
+		"29a6bfdaeed4d045",
+	),
+	(
+		"AUvHAkUQvzpFNI6GwQrtN8FMw8FC/+weJhOKAQAA/wFYAg==",
+		"25862d3de1065c7d",
+	),
+	(
+		"AI+tokSa9XZEvJh2QeeFjcE476nB/0AfJhOKAQAA/wFYAg==",
+		"a17966dcc4e095be",
+	),
+	(
+		"AZGEOkSiaKlEbMNBQYjhlcDmK77CAZIfJhOKAQAA/wFYAg==",
+		"502f8c2d1fe87a0e",
+	),
+	(
+		"ANtY10QCSYLEsG5FQdCfgkAKj7fC/+UfJhOKAQAA/wFYAg==",
+		"b46aefdb0ca8dfc4",
+	),
+	(
+		"AN6SFMX+DfVELiOVwXim70CrLGzC/zggJhOKAQAA/wFYAg==",
+		"2784bb60a2b349f6",
+	),
+	(
+		"AEiDwMTquHBFwMlov2pkUcHk89DB/4wgJhOKAQAA/wFYAg==",
+		"760c1b3c7eef5d36",
+	),
+	(
+		"ARDnrUPMNQfEr3g5wQAJv770pSTC/+AgJhOKAQAA/wFYAg==",
+		"29e089a647a8c4b6",
+	),
+	(
+		"AYSEtkRw0PVEqBWSwTRUOEF4oHVBATQhJhOKAQAA/wFYAg==",
+		"83893dbe7958ef32",
+	),
+	(
+		"AT2mmkQuC0xE6MASQPDRBEEQfe3A/4YhJhOKAQAA/wFYAg==",
+		"bf095d8a5ad431c5",
+	),
+	(
+		"Aacg1kTiD3NFDYxUwczUmkDAp/xBAdghJhOKAQAA/wFYAg==",
+		"d0ed335f6aaad6d2",
+	),
+	(
+		"AAj1n0OceS3EnuN/wYr2HEHysATC/ykiJhOKAQAA/wFYAg==",
+		"b8fc04773a82c0fa",
+	),
+	(
+		"ABGPukRMnSlFAIGRQQCq/b1Yc6dCAX4iJhOKAQAA/wFYAg==",
+		"3f21421a4dbbbeaf",
+	),
+	(
+		"AWCgdER8VilF+gGOwRpWgEG0fRXCAdEiJhOKAQAA/wFYAg==",
+		"98b4a91f6205cedb",
+	),
+	(
+		"AfvAsEXOVStEfJGDQdZEiMHQcpXA/yUjJhOKAQAA/wFYAg==",
+		"d09611f6c041f69",
+	),
+	(
+		"AAhqSENCDWFFINRKQQKCD8H4TaZBAHojJhOKAQAA/wFYAg==",
+		"8a2f5a5ff424be55",
+	),
+	(
+		"AP4fwsTKuwZFatiPwEZDCkF0gC3CAcsjJhOKAQAA/wFYAg==",
+		"87e9c280f2a8a28",
+	),
+	(
+		"ADAi3EQsmEZFCBiUQQ7kQcHiNlnCAB4kJhOKAQAA/wFYAg==",
+		"330b8c3476fc26a6",
+	),
+	(
+		"AVNxKUUDw3hFPK1jQeDr7j80ExRC/28kJhOKAQAA/wFYAg==",
+		"ded90d72ba0d2c25",
+	),
+	(
+		"AAViDUUN/ZxEEEVmwEh8lkFgaAlC/8EkJhOKAQAA/wFYAg==",
+		"162a7953874fed57",
+	),
+	(
+		"AHy8kkWswnhEOIIaQJD1IcDErWhCABMlJhOKAQAA/wFYAg==",
+		"96ce27b365d61485",
+	),
+	(
+		"AIiepUQMzWZEnCt8QXsyRcEg6BHCAWYlJhOKAQAA/wFYAg==",
+		"8c6730f82a02ce01",
+	),
+	(
+		"AaLdP0W91hxF/RwzwRIki0Hwe0FCALklJhOKAQAA/wFYAg==",
+		"c318dc94c28e5938",
+	),
+	(
+		"AYRqfURuL85EEvq7wM69DEH44x3CAcIxJhOKAQAA/wFYAg==",
+		"b022a09f4400127",
+	),
+	(
+		"ARaAjkQSuvFEWjpFwaYBc8HIWT1CABQyJhOKAQAA/wFYAg==",
+		"83268ba6c4dececb",
+	),
+	(
+		"AK5JBkWaOYpFmJKsQPYkLcHcv6tCAAY+JhOKAQAA/wFYAg==",
+		"aef633a19b01f4fd",
+	),
+	(
+		"ABybcMQShINFaOX+QJAaOcEINs5B/1s+JhOKAQAA/wFYAg==",
+		"d89ce70401753a19",
+	),
+	(
+		"AfKL+EM8k5/DTsyRwcJeQMGACRNCAK8+JhOKAQAA/wFYAg==",
+		"3813fddd0dcbb711",
+	),
+	(
+		"ARzXiUPXM4pEuNvvQKwicEFoGnRCAKJKJhOKAQAA/wFYAg==",
+		"49198692aa26db15",
+	),
+	(
+		"AcxqmsTsOB1FvnR5wawL8sAwPhLBASdXJhOKAQAA/wFYAg==",
+		"e79633efc94601cf",
+	),
+	(
+		"AG4eO0UiaN1EAOWIPcwE40BEER5C/9FXJhOKAQAA/wFYAg==",
+		"cc46c9b2c22ee4a5",
+	),
+	(
+		"ASTplsTw+jHESriQwaD7REFgihVCAIJYJhOKAQAA/wFYAg==",
+		"da13d79dbc18a455",
+	),
+	(
+		"AYJ4hMTM3nfE4EaAQIDvCkDQRmPB/4dxJhOKAQAA/wFYAg==",
+		"978b98ceb238cc",
+	),
+	(
+		"ALDtRcTiHdnEbPD8QGALi0EIOMTBADlyJhOKAQAA/wFYAg==",
+		"844882af47a448f5",
+	),
+	(
+		"AHikpET3uqdEkP/dwKB6fUGAO1VAAPByJhOKAQAA/wFYAg==",
+		"6eb1afba02b59e5e",
+	),
+	(
+		"ASAfisMzIW5FYMeYP85MWsGQ7hTCAKlzJhOKAQAA/wFYAg==",
+		"c1617365598c21bb",
+	),
+	(
+		"APhKSUXxw8ZEhINzQVi2VUEsdrVC/9SLJhOKAQAA/wFYAg==",
+		"3dc183133dd24494",
+	),
+	(
+		"ANQOXkUcUQRFsMdIQGyxiUGRIIPCAIeMJhOKAQAA/wFYAg==",
+		"3e644c72194aea79",
+	),
+	(
+		"AZL5E0VhTulEJIuKwaDI9z8WpLNC/zqNJhOKAQAA/wFYAg==",
+		"37709d7ded8a27d0",
+	),
+	(
+		"AYGZsEQYHgfFOHOuQGqdu8CU4lvCAO+NJhOKAQAA/wFYAg==",
+		"fa88cd776847b62e",
+	),
+	(
+		"AWA/OMOOnGJEwAzYPr5h0MBcHbrB/4qlJhOKAQAA/wFYAg==",
+		"46a036d2a14a2e88",
+	),
+];
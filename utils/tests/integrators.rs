@@ -0,0 +1,57 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+//a test binary of its own since it sets a different map than the other tests
+
+use std::f32::consts::PI;
+use utils::map::{set_map, Map};
+use utils::scalar::{Real, Scalar};
+use utils::trajectory::{Integrator, Trajectory, Vector};
+
+//smallest and largest distance from the body during about ten circular orbits
+fn orbit_extremes(integrator: Integrator) -> (f32, f32) {
+	let mut map = Map::from_toml(
+		"dome_radius = 6000.0\n[[bodies]]\npos = { x = 0.0, y = 0.0 }\nradius = 100.0\n",
+	)
+	.unwrap();
+	map.integrator = integrator;
+	set_map(map);
+	let distance = 1500.0;
+	let speed = (2000.0 * 100.0 * 100.0 * PI / distance).sqrt(); //G * mass / distance
+	let mut ship = Trajectory {
+		pos: Vector {
+			x: Scalar::from_f32(distance),
+			y: Scalar::default(),
+		},
+		vel: Vector {
+			x: Scalar::default(),
+			y: Scalar::from_f32(speed),
+		},
+		time: 0,
+		health: 100,
+		boosters: 1,
+		..Default::default()
+	};
+	let (mut closest, mut furthest) = (f32::MAX, 0f32);
+	for _ in 0..4600 {
+		ship.step();
+		let radius = ship.pos.mag().to_f32();
+		closest = closest.min(radius);
+		furthest = furthest.max(radius);
+	}
+	(closest, furthest)
+}
+
+#[test]
+fn symplectic_integrators_keep_orbits_circular() {
+	let (closest, furthest) = orbit_extremes(Integrator::Euler);
+	assert!(furthest - closest > 15.0);
+	for integrator in [Integrator::Verlet, Integrator::Leapfrog] {
+		let (closest, furthest) = orbit_extremes(integrator);
+		assert!(closest > 1498.5 && furthest < 1501.5, "{:?}", integrator);
+	}
+	assert_eq!(
+		"leapfrog".parse::<Integrator>().unwrap(),
+		Integrator::Leapfrog
+	);
+	assert!("rk4".parse::<Integrator>().is_err());
+}
@@ -1,21 +1,35 @@
 #![cfg(not(target_arch = "wasm32"))]
 
-use rand::Rng;
-use utils::trajectory::Trajectory;
+mod common;
+
+use common::{load_default_map, ship};
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use utils::fixed::Fixed;
+use utils::gravity::GravityGrid;
+use utils::history::{advance_together, ShipContact, TrajectoryHistory};
+use utils::map::Map;
+use utils::scalar::{Real, Scalar};
+use utils::trajectory::{Body, Trajectory, UpdateType, UpdateTypeWrapper, Vector, MAX_FUEL};
 
 #[test]
 fn test_some_function() {
+	load_default_map();
 	let mut rng = rand::thread_rng();
 
 	const RUNS: u32 = 10000000;
 	const NUM_TESTS: i32 = 50;
 	for _ in 1..NUM_TESTS {
-		let mut example = Trajectory::default();
-		example.propelling = rng.gen::<u8>() > 128;
-		example.spin = rng.gen_range(-100f32..100f32);
-		example.spin_direction = rng.gen_range(-1..2);
-		example.vel.x = rng.gen_range(-20f32..20f32);
-		example.vel.y = rng.gen_range(-20f32..20f32);
+		let mut example = Trajectory {
+			propelling: rng.gen::<u8>() > 128,
+			spin: Scalar::from_f32(rng.gen_range(-100f32..100f32)),
+			spin_direction: rng.gen_range(-1..2),
+			vel: Vector {
+				x: Scalar::from_f32(rng.gen_range(-20f32..20f32)),
+				y: Scalar::from_f32(rng.gen_range(-20f32..20f32)),
+			},
+			..Default::default()
+		};
 		println!(
 			"	let mut testing = Trajectory::from_b64(\"{}\".to_string());",
 			example.to_b64()
@@ -33,3 +47,332 @@ fn test_some_function() {
 	}
 	assert_eq!(1 + 1, 2);
 }
+
+#[test]
+fn map_hash_survives_json_roundtrip() {
+	let map = Map::from_toml(include_str!("../../maps/default.toml")).unwrap();
+	let received = Map::from_json(&map.to_json()).unwrap();
+	assert_eq!(map.bodies.len(), 25);
+	assert_eq!(map.hash_str(), received.hash_str());
+	assert!(Map::from_json(r#"{"dome_radius": -1.0, "bodies": []}"#).is_err());
+}
+
+#[test]
+fn orbits_repeat_every_period() {
+	let map = Map::from_toml(include_str!("../../maps/moons.toml")).unwrap();
+	let start = map.bodies_at(1_700_000_000_000);
+	let later = map.bodies_at(1_700_000_000_000 + 40000 * 75000 * 9000 * 60000);
+	for (a, b) in start.iter().zip(later.iter()) {
+		assert_eq!(a.pos.x.to_bits(), b.pos.x.to_bits());
+		assert_eq!(a.pos.y.to_bits(), b.pos.y.to_bits());
+	}
+	//the binary stars sit opposite each other around their shared centre
+	let (x, y) = (
+		start[4].pos.x + start[5].pos.x,
+		start[4].pos.y + start[5].pos.y,
+	);
+	assert!((x.to_f32() - 5000.0).abs() < 1.0);
+	assert!((y.to_f32() + 3000.0).abs() < 1.0);
+}
+
+#[test]
+fn fixed_point_math() {
+	for i in -2000..2000 {
+		let angle = i as f64 * 0.01;
+		let fixed = Fixed::from_f64(angle);
+		assert!((fixed.sin().to_f64() - angle.sin()).abs() < 1e-6);
+		assert!((fixed.cos().to_f64() - angle.cos()).abs() < 1e-6);
+	}
+	for value in [0.25, 2.0, 1234.5, 36000000.0] {
+		let root = Fixed::from_f64(value).sqrt().to_f64();
+		assert!((root - f64::sqrt(value)).abs() < 1e-6);
+	}
+	let third = Fixed::from_f64(1.0) / Fixed::from_f64(3.0);
+	assert_eq!(third, Fixed::from_ratio(1, 3));
+	assert_eq!(Fixed::from_f64(-7.5) / Fixed::ZERO, Fixed::MIN);
+}
+
+#[test]
+fn trajectory_hash_is_pinned() {
+	let example = Trajectory {
+		propelling: true,
+		pos: Vector {
+			x: Scalar::from_f32(1500.0),
+			y: Scalar::from_f32(-250.5),
+		},
+		vel: Vector {
+			x: Scalar::from_f32(3.25),
+			y: Scalar::default(),
+		},
+		spin: Scalar::from_f32(1.0),
+		spin_direction: -1,
+		time: 1_700_000_000_000,
+		health: 200,
+		boosters: 2,
+		fuel: 345,
+	};
+	//changing this value means every deployed client disagrees, bump HASH_VERSION with it
+	#[cfg(not(feature = "fixed-point"))]
+	assert_eq!(example.hash_str(), "311aa75fb1b278fc");
+	let more_boosters = Trajectory {
+		boosters: 3,
+		..example.clone()
+	};
+	assert_ne!(example.hash_str(), more_boosters.hash_str());
+	let less_fuel = Trajectory {
+		fuel: 344,
+		..example.clone()
+	};
+	assert_ne!(example.hash_str(), less_fuel.hash_str());
+}
+
+#[test]
+fn fast_ships_cannot_pass_through_bodies() {
+	load_default_map();
+	//one step takes the ship from one side of the radius 57 body at (3460, -1882) to the other
+	let mut fast = Trajectory {
+		vel: Vector {
+			x: Scalar::from_f32(6000.0),
+			y: Scalar::default(),
+		},
+		..ship(3160.0, -1882.0)
+	};
+	fast.step();
+	assert!(fast.pos.x.to_f32() > 3460.0 + 57.0 + 25.0);
+	assert_eq!(fast.health, 0);
+}
+
+#[test]
+fn dome_stops_ships_at_the_wall() {
+	load_default_map();
+	let mut fast = Trajectory {
+		vel: Vector {
+			x: Scalar::from_f32(8000.0),
+			y: Scalar::default(),
+		},
+		..ship(5500.0, 0.0)
+	};
+	fast.step();
+	assert!((fast.pos.x.to_f32() - 6000.0).abs() < 0.01);
+	assert!(fast.vel.x.to_f32() < 0.0);
+	assert_eq!(fast.health, 100);
+}
+
+#[test]
+fn gravity_grid_is_close_to_exact_sum() {
+	let mut rng = SmallRng::seed_from_u64(7);
+	let bodies: Vec<Body> = (0..400)
+		.map(|_| Body {
+			pos: Vector {
+				x: Scalar::from_f32(rng.gen_range(-20000f32..20000f32)),
+				y: Scalar::from_f32(rng.gen_range(-20000f32..20000f32)),
+			},
+			radius: Scalar::from_f32(rng.gen_range(50f32..150f32)),
+			orbit: None,
+			outline: None,
+		})
+		.collect();
+	let grid = GravityGrid::new(&bodies, Scalar::from_f32(20000.0));
+	let mut total_relative = 0.0;
+	for _ in 0..500 {
+		let pos = Vector {
+			x: Scalar::from_f32(rng.gen_range(-20000f32..20000f32)),
+			y: Scalar::from_f32(rng.gen_range(-20000f32..20000f32)),
+		};
+		//pull_sum is scaled to one step of 100ms
+		let exact = Trajectory::pull_sum(&bodies, &pos);
+		let approx = grid.acceleration(&bodies, &pos);
+		let (ex, ey) = (exact.x.to_f32() * 10.0, exact.y.to_f32() * 10.0);
+		let (ax, ay) = (approx.x.to_f32(), approx.y.to_f32());
+		let error = ((ax - ex) * (ax - ex) + (ay - ey) * (ay - ey)).sqrt();
+		let magnitude = (ex * ex + ey * ey).sqrt();
+		//where the pulls nearly cancel out only the absolute error stays small
+		assert!(error < 0.05 * magnitude + 0.5);
+		total_relative += error / magnitude;
+	}
+	assert!(total_relative / 500.0 < 0.01);
+}
+
+#[test]
+fn prediction_follows_step() {
+	load_default_map();
+	let turning = Trajectory {
+		propelling: true,
+		vel: Vector {
+			x: Scalar::from_f32(30.0),
+			y: Scalar::from_f32(-10.0),
+		},
+		spin: Scalar::from_f32(0.5),
+		spin_direction: 1,
+		time: 1_700_000_000_000,
+		..ship(-1000.0, 400.0)
+	};
+	let prediction = turning.predict(50);
+	let mut stepped = turning.clone();
+	for point in prediction.path.chunks(2) {
+		stepped.step();
+		assert_eq!(point, [stepped.pos.x.to_f32(), stepped.pos.y.to_f32()]);
+	}
+	assert_eq!(turning.time, 1_700_000_000_000);
+
+	//the fast ship from fast_ships_cannot_pass_through_bodies dies in its first step
+	let doomed = Trajectory {
+		vel: Vector {
+			x: Scalar::from_f32(6000.0),
+			y: Scalar::default(),
+		},
+		time: 5000,
+		..ship(3160.0, -1882.0)
+	};
+	let prediction = doomed.predict(50);
+	assert_eq!(prediction.path.len(), 2);
+	let crash = prediction.crash.unwrap();
+	assert_eq!(crash.time, 5100);
+	assert_eq!([crash.x, crash.y], prediction.path[..]);
+}
+
+#[test]
+fn propelling_burns_fuel() {
+	load_default_map();
+	let mut ship = Trajectory {
+		propelling: true,
+		boosters: 2,
+		fuel: 5,
+		..ship(-1000.0, 400.0)
+	};
+	ship.step();
+	assert_eq!(ship.fuel, 3);
+	assert!(ship.propelling);
+	ship.step();
+	ship.step();
+	//the last step only had fuel for one booster
+	assert_eq!(ship.fuel, 0);
+	assert!(!ship.propelling);
+	ship.step();
+	assert_eq!(ship.fuel, 0);
+
+	//an empty tank can't be switched on, refuelling fixes that
+	let change = |utype, value| UpdateTypeWrapper { utype, value };
+	ship.apply_change(change(UpdateType::PropOn, None));
+	assert!(!ship.propelling);
+	ship.apply_change(change(UpdateType::Refuel, Some(50)));
+	assert_eq!(ship.fuel, MAX_FUEL / 2);
+	ship.apply_change(change(UpdateType::Refuel, Some(255)));
+	assert_eq!(ship.fuel, MAX_FUEL);
+	ship.apply_change(change(UpdateType::PropOn, None));
+	assert!(ship.propelling);
+}
+
+#[test]
+fn ships_bounce_off_each_other() {
+	load_default_map();
+	let moving = |x: f32, vel: f32, time: u64| Trajectory {
+		vel: Vector {
+			x: Scalar::from_f32(vel),
+			y: Scalar::default(),
+		},
+		time,
+		health: 200,
+		..ship(x, -2500.0)
+	};
+	//closing in at 400 per second, 30 apart after the first step of the earlier ship
+	let mut left = moving(-45.0, 200.0, 1000);
+	let mut right = moving(45.0, -200.0, 1050);
+	let mut far = moving(2000.0, 0.0, 1000);
+	let mut histories = [&left, &right, &far].map(TrajectoryHistory::new);
+	let [a, b, c] = &mut histories;
+	let contacts = advance_together(
+		&mut [(&mut left, a), (&mut right, b), (&mut far, c)],
+		1000,
+		1500,
+	);
+	assert_eq!(contacts.len(), 1);
+	let ShipContact {
+		first,
+		second,
+		damage,
+		..
+	} = contacts[0];
+	assert_eq!((first, second), (0, 1));
+	//gravity changes the closing speed a little
+	assert!((38..=41).contains(&damage));
+	assert!(left.vel.x.to_f32() < 0.0 && right.vel.x.to_f32() > 0.0);
+	assert_eq!(
+		(left.health, right.health, far.health),
+		(200 - damage, 200 - damage, 200)
+	);
+	assert_eq!((left.time, right.time, far.time), (1500, 1450, 1500));
+	assert!(right.pos.x.to_f32() - left.pos.x.to_f32() > 2.0 * 25.0);
+}
+
+#[test]
+fn ramming_can_kill() {
+	load_default_map();
+	let moving = |x: f32, vel: f32| Trajectory {
+		vel: Vector {
+			x: Scalar::from_f32(vel),
+			y: Scalar::default(),
+		},
+		time: 1000,
+		health: 30,
+		..ship(x, -2500.0)
+	};
+	let mut left = moving(-45.0, 200.0);
+	let mut right = moving(45.0, -200.0);
+	let [mut a, mut b] = [&left, &right].map(TrajectoryHistory::new);
+	let contacts = advance_together(&mut [(&mut left, &mut a), (&mut right, &mut b)], 1000, 1500);
+	//the server's tick sees both at 0 health and announces the deaths with their loot
+	assert_eq!(contacts.len(), 1);
+	assert_eq!((left.health, right.health), (0, 0));
+	let wrecks = (left.hash_str(), right.hash_str());
+	let mut ships = [(&mut left, &mut a), (&mut right, &mut b)];
+	assert!(advance_together(&mut ships, 1500, 3000).is_empty());
+	assert_eq!((left.hash_str(), right.hash_str()), wrecks);
+}
+
+#[test]
+fn ships_ahead_of_the_others_still_bounce() {
+	load_default_map();
+	let moving = |x: f32, vel: f32| Trajectory {
+		vel: Vector {
+			x: Scalar::from_f32(vel),
+			y: Scalar::default(),
+		},
+		time: 1000,
+		health: 200,
+		..ship(x, -2500.0)
+	};
+	let mut left = moving(-45.0, 200.0);
+	let mut right = moving(45.0, -200.0);
+	let mut left_history = TrajectoryHistory::new(&left);
+	let mut right_history = TrajectoryHistory::new(&right);
+	//the right one sent an update from past the horizon before the left one got there
+	let turn = UpdateTypeWrapper {
+		utype: UpdateType::RotCw,
+		value: None,
+	};
+	right.advance_to_time(1300);
+	right.apply_change(turn.clone());
+	right_history.record(&right, Some(turn));
+	right.advance_to_time(2000);
+	assert!(right.vel.x.to_f32() < 0.0);
+
+	let contacts = advance_together(
+		&mut [
+			(&mut left, &mut left_history),
+			(&mut right, &mut right_history),
+		],
+		1000,
+		1500,
+	);
+	assert_eq!(contacts.len(), 1);
+	assert!(contacts[0].after[1].time < 1300);
+	//it bounced back where they touched and still turned when it said it did
+	assert_eq!((left.time, right.time), (1500, 2000));
+	assert!(left.vel.x.to_f32() < 0.0 && right.vel.x.to_f32() > 0.0);
+	assert_eq!(right.spin_direction, 1);
+	assert_eq!(right.health, 200 - contacts[0].damage);
+	let replayed = right_history.at(2000).unwrap();
+	assert_eq!(replayed.hash_str(), right.hash_str());
+	assert_eq!(right_history.at(1250).unwrap().spin_direction, 0);
+}
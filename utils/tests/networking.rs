@@ -0,0 +1,352 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+mod common;
+
+use base64::{engine::general_purpose, Engine as _};
+use common::{load_default_map, ship};
+use utils::conduct::{Action, Offence, Policy, Watchlist};
+use utils::delta::{SnapshotLog, MAX_BEHIND};
+use utils::interest::{Interest, Spot, VIEW_RADIUS};
+use utils::limits::{Limiter, Limits, Rate, Verdict};
+use utils::messages::{ClientMessage, Color, PlayerView, ServerMessage, ShootInfo};
+use utils::protocol::{from_binary, to_binary, Codec, PROTOCOL_VERSION};
+use utils::trajectory::{UpdateType, UpdateTypeWrapper};
+
+#[test]
+fn players_only_see_what_is_near() {
+	let spot = |id: &str, x: f32| Spot {
+		id: id.to_string(),
+		x,
+		y: 0.0,
+	};
+	let mut interest = Interest::default();
+	let ships = |b: f32, e: f32| {
+		vec![
+			(spot("a", 0.0), true),
+			(spot("b", b), true),
+			(spot("c", 3.0 * VIEW_RADIUS), true),
+			(spot("dead", 100.0), false),
+			(spot("e", e), true),
+		]
+	};
+	let loot = vec![spot("near", 50.0), spot("far", 3.0 * VIEW_RADIUS - 50.0)];
+	let changes = interest.refresh(ships(1000.0, 10.0 * VIEW_RADIUS), loot.clone());
+	let view = interest.view("a").unwrap();
+	assert_eq!(view.players.len(), 1);
+	assert!(view.players.contains("b"));
+	assert!(view.loot.contains("near") && !view.loot.contains("far"));
+	assert_eq!(changes["a"].entered, *view);
+	//the dead can't be seen but still look around
+	assert!(interest.view("dead").unwrap().players.contains("a"));
+	assert!(interest.view("c").unwrap().loot.contains("far"));
+
+	//events go to the players involved, whoever sees them and whoever sees the place
+	let audience = interest.audience(&["b"], None);
+	assert!(["a", "b", "dead"].iter().all(|id| audience.contains(*id)));
+	assert!(!audience.contains("c"));
+	let audience = interest.audience(&[], Some((3.0 * VIEW_RADIUS, 0.0)));
+	assert!(audience.contains("c") && !audience.contains("a"));
+
+	//just past the edge, something already seen stays while something new isn't let in
+	let edge = VIEW_RADIUS * 1.05;
+	let changes = interest.refresh(ships(edge, edge), loot.clone());
+	let view = interest.view("a").unwrap();
+	assert!(view.players.contains("b") && !view.players.contains("e"));
+	assert!(!changes.contains_key("a"));
+	let changes = interest.refresh(ships(2.0 * VIEW_RADIUS, edge), loot);
+	assert!(changes["a"].left.players.contains("b"));
+	assert!(changes["b"].left.players.contains("a"));
+}
+
+#[test]
+fn binary_frames_carry_typed_messages() {
+	load_default_map();
+	let update = ClientMessage::TrajectoryUpdate {
+		change: UpdateTypeWrapper {
+			utype: UpdateType::PropOn,
+			value: None,
+		},
+		at: "ab".to_string(),
+		time: 7,
+	};
+	let frame = to_binary(&update).unwrap();
+	assert_eq!(frame[0], PROTOCOL_VERSION);
+	assert!(frame.len() < serde_json::to_string(&update).unwrap().len());
+	match from_binary::<ClientMessage>(&frame).unwrap() {
+		ClientMessage::TrajectoryUpdate { change, at, time } => {
+			assert!(matches!(change.utype, UpdateType::PropOn));
+			assert_eq!((at.as_str(), time), ("ab", 7));
+		}
+		other => panic!("decoded as {:?}", other),
+	}
+	//what the javascript client writes reads the same
+	let json = r#"{"t":"TrajectoryUpdate","c":{"change":{"utype":"PropOn"},"at":"ab","time":7}}"#;
+	let written: ClientMessage = serde_json::from_str(json).unwrap();
+	assert_eq!(to_binary(&written).unwrap(), frame);
+
+	//trajectories are their bytes in binary frames and base64 in JSON
+	let player = PlayerView {
+		name: "a".to_string(),
+		id: "a".to_string(),
+		color: Color { r: 1, g: 2, b: 3 },
+		trajectory: ship(100.0, -50.0),
+		inventory: None,
+		cash: Some(20),
+	};
+	let bytes = bincode::serialize(&player.trajectory).unwrap();
+	let join = ServerMessage::PlayerJoin(player);
+	let frame = to_binary(&join).unwrap();
+	assert!(frame.windows(bytes.len()).any(|window| window == bytes));
+	let text = serde_json::to_string(&join).unwrap();
+	assert!(text.contains(&general_purpose::STANDARD.encode(&bytes)));
+	assert!(text.contains(r#""cash":20"#) && !text.contains("inventory"));
+	assert!(frame.len() < text.len());
+	for decoded in [
+		from_binary(&frame).unwrap(),
+		serde_json::from_str(&text).unwrap(),
+	] {
+		match decoded {
+			ServerMessage::PlayerJoin(view) => {
+				assert_eq!(view.trajectory.hash_str(), ship(100.0, -50.0).hash_str());
+				assert_eq!((view.cash, view.inventory.is_none()), (Some(20), true));
+			}
+			other => panic!("decoded as {:?}", other),
+		}
+	}
+	let ping = to_binary(&ClientMessage::Ping).unwrap();
+	assert!(matches!(from_binary(&ping), Ok(ClientMessage::Ping)));
+
+	//frames of another version are refused instead of misread
+	let mut other = frame.clone();
+	other[0] = PROTOCOL_VERSION + 1;
+	assert!(from_binary::<ClientMessage>(&other).is_err());
+	assert!(from_binary::<ClientMessage>(&[]).is_err());
+
+	//the first of the offered protocols spoken here wins
+	let binary = Codec::Binary.name();
+	let offered = format!("binary.0, {}, json", binary);
+	assert_eq!(Codec::negotiate(&offered), Some(Codec::Binary));
+	assert_eq!(Codec::negotiate("json, binary.0"), Some(Codec::Json));
+	assert_eq!(Codec::negotiate("binary.0"), None);
+}
+
+#[test]
+fn every_message_is_named() {
+	let all = [
+		ClientMessage::Ping,
+		ClientMessage::AckPong,
+		ClientMessage::TrajectoryUpdate {
+			change: UpdateTypeWrapper {
+				utype: UpdateType::PropOn,
+				value: None,
+			},
+			at: String::new(),
+			time: 0,
+		},
+		ClientMessage::ChangeSlot { slot: 0 },
+		ClientMessage::ClaimLoot {
+			loot_id: String::new(),
+		},
+		ClientMessage::Correct(String::new()),
+		ClientMessage::TriggerPress { time: 0 },
+		ClientMessage::Shoot(ShootInfo {
+			at: String::new(),
+			stime: 0,
+			shooter: None,
+			victim: None,
+		}),
+		ClientMessage::StateQuery,
+		ClientMessage::AckSnapshot { sequence: 0 },
+		ClientMessage::Spawn,
+	];
+	//limits are checked against NAMES, it has to have each of them once
+	assert_eq!(all.len(), ClientMessage::NAMES.len());
+	for (message, name) in all.iter().zip(ClientMessage::NAMES) {
+		assert_eq!(message.name(), name);
+		//and the name is the tag it is sent with
+		let json = serde_json::to_value(message).unwrap();
+		assert_eq!(json["t"], name);
+	}
+}
+
+#[test]
+fn snapshots_only_list_changes_since_the_acknowledged_one() {
+	let world = |players: &[(&str, u64)], loot: &[&str]| {
+		(
+			players
+				.iter()
+				.map(|(id, revision)| (id.to_string(), *revision))
+				.collect(),
+			loot.iter().map(|id| id.to_string()).collect(),
+		)
+	};
+	let sorted = |mut ids: Vec<String>| {
+		ids.sort();
+		ids
+	};
+	let mut log = SnapshotLog::default();
+	let (players, loot) = world(&[("a", 1), ("b", 1)], &["x"]);
+	let first = log.next(players, loot);
+	assert_eq!(first.base, None);
+	assert_eq!(sorted(first.players), ["a", "b"]);
+	//everything until one is acknowledged
+	let (players, loot) = world(&[("a", 1), ("b", 1)], &["x"]);
+	assert_eq!(log.next(players, loot).base, None);
+	assert!(log.ack(first.sequence));
+
+	let (players, loot) = world(&[("a", 2), ("b", 1)], &["y"]);
+	let delta = log.next(players, loot);
+	assert_eq!(delta.base, Some(first.sequence));
+	assert_eq!(delta.players, ["a"]);
+	assert_eq!(
+		(delta.loot, delta.taken),
+		(vec!["y".to_string()], vec!["x".to_string()])
+	);
+	assert!(log.ack(delta.sequence));
+	//b left in a snapshot that wasn't acknowledged yet, so coming back it is sent again
+	let (players, loot) = world(&[("a", 2)], &["y"]);
+	assert_eq!(log.next(players, loot).gone, ["b"]);
+	let (players, loot) = world(&[("a", 2), ("b", 1)], &["y"]);
+	let delta = log.next(players, loot);
+	assert_eq!(delta.players, ["b"]);
+	assert!(delta.gone.is_empty() && delta.loot.is_empty());
+	assert!(log.ack(delta.sequence));
+	let (players, loot) = world(&[("a", 2), ("b", 1)], &["y"]);
+	let delta = log.next(players, loot);
+	assert!(delta.players.is_empty() && delta.loot.is_empty() && delta.taken.is_empty());
+
+	//acknowledging what was never sent or is already built on changes nothing
+	assert!(!log.ack(first.sequence) && !log.ack(delta.sequence + 1));
+	//a client that stops acknowledging is sent everything again
+	for _ in 0..MAX_BEHIND {
+		let (players, loot) = world(&[("a", 2), ("b", 1)], &["y"]);
+		log.next(players, loot);
+	}
+	let (players, loot) = world(&[("a", 2), ("b", 1)], &["y"]);
+	let resync = log.next(players, loot);
+	assert_eq!(resync.base, None);
+	assert_eq!(sorted(resync.players), ["a", "b"]);
+}
+
+#[test]
+fn limiter_drops_floods_and_disconnects_repeat_offenders() {
+	let limits = Limits {
+		rates: [("Shoot".to_string(), Rate::new(2.0, 3.0))].into(),
+		total: Rate::new(100.0, 100.0),
+		tolerance: Rate::new(0.1, 5.0),
+		max_frame: 100,
+	};
+	let mut limiter = Limiter::new(&limits, 0);
+	let verdicts: Vec<Verdict> = (0..4).map(|_| limiter.allow("Shoot", 0)).collect();
+	assert_eq!(verdicts[..3], [Verdict::Allowed; 3]);
+	assert_eq!(verdicts[3], Verdict::Limited);
+	//messages without a limit of their own only count towards the total
+	assert_eq!(limiter.allow("Ping", 0), Verdict::Allowed);
+	//the bucket refills at its rate
+	assert_eq!(limiter.allow("Shoot", 500), Verdict::Allowed);
+	assert_eq!(limiter.allow("Shoot", 500), Verdict::Limited);
+	assert_eq!(limiter.hits()["Shoot"], 2);
+	//going over again and again empties the tolerance
+	let verdicts: Vec<Verdict> = (0..4).map(|_| limiter.allow("Shoot", 500)).collect();
+	assert_eq!(verdicts[..3], [Verdict::Limited; 3]);
+	assert_eq!(verdicts[3], Verdict::Disconnect);
+
+	let mut limiter = Limiter::new(&limits, 0);
+	let flood = (0..101).map(|_| limiter.allow("Invalid", 0)).last();
+	assert_eq!(flood, Some(Verdict::Limited));
+	assert_eq!(limiter.hits()["total"], 1);
+}
+
+#[test]
+fn snapshot_acks_keep_up_with_any_tick_rate() {
+	let limits = Limits::default().paced(1000);
+	assert_eq!(limits.rates["AckSnapshot"], Rate::new(1000.0, 2000.0));
+	let mut limiter = Limiter::new(&limits, 0);
+	//a second of acks at the highest tick rate, with everything else at its default limit
+	let acks = (0..1000)
+		.map(|i| limiter.allow("AckSnapshot", i))
+		.all(|v| v == Verdict::Allowed);
+	assert!(acks);
+	let updates = (0..20).all(|_| limiter.allow("TrajectoryUpdate", 1000) == Verdict::Allowed);
+	assert!(updates);
+	assert!(limiter.hits().is_empty());
+}
+
+#[test]
+fn suspicion_builds_up_and_fades() {
+	let mut watchlist = Watchlist::new(Policy::default());
+	let purchase = || Offence::UnaffordablePurchase { cost: 10, cash: 0 };
+	let actions: Vec<Option<Action>> = (0..10)
+		.map(|_| watchlist.report("cheat", purchase(), 0))
+		.collect();
+	//each action is taken once, when its threshold is reached
+	assert_eq!(actions[1], Some(Action::Warn));
+	assert_eq!(actions[4], Some(Action::ShadowMute));
+	assert_eq!(actions[9], Some(Action::Kick));
+	assert_eq!(actions.iter().flatten().count(), 3);
+	assert!(watchlist.muted("cheat", 0));
+	watchlist.report(
+		"laggy",
+		Offence::RejectedUpdate {
+			reason: "Hash mismatch!".to_string(),
+		},
+		0,
+	);
+	assert_eq!(watchlist.action("laggy", 0), None);
+
+	//a minute later half of it is forgiven
+	let minute = Policy::default().half_life;
+	assert_eq!(watchlist.action("cheat", minute), Some(Action::ShadowMute));
+	let flagged = watchlist.flagged(minute);
+	assert_eq!(flagged.len(), 1);
+	assert_eq!(
+		(flagged[0].id.as_str(), flagged[0].action),
+		("cheat", Action::Kick)
+	);
+	assert_eq!(flagged[0].counts["UnaffordablePurchase"], 10);
+	//and once it fell below a threshold, reaching it again acts again
+	assert_eq!(watchlist.report("cheat", purchase(), 2 * minute), None);
+	assert_eq!(watchlist.report("cheat", purchase(), 2 * minute), None);
+	assert_eq!(
+		watchlist.report("cheat", purchase(), 2 * minute),
+		Some(Action::ShadowMute)
+	);
+	assert!(!watchlist.muted("cheat", 10 * minute));
+	//the rest is forgotten with the next report
+	watchlist.report("other", purchase(), 20 * minute);
+	assert_eq!(watchlist.flagged(20 * minute).len(), 0);
+	assert_eq!(watchlist.action("laggy", 20 * minute), None);
+}
+
+#[test]
+fn limits_and_policy_read_from_toml() {
+	let limits: Limits =
+		toml::from_str("max_frame = 8192\n[rates]\nShoot = { per_second = 5, burst = 5 }").unwrap();
+	//listed rates replace their defaults and the rest keep them
+	assert_eq!(limits.rates["Shoot"], Rate::new(5.0, 5.0));
+	assert_eq!(limits.rates["Ping"], Limits::default().rates["Ping"]);
+	assert_eq!(limits.total, Limits::default().total);
+	assert_eq!(limits.max_frame, 8192);
+	assert!(limits.validate().is_ok());
+	assert!(toml::from_str::<Limits>("max_frames = 1").is_err());
+	//a misspelt name would limit nothing
+	let misspelt: Limits =
+		toml::from_str("rates = { Shout = { per_second = 1, burst = 1 } }").unwrap();
+	assert!(misspelt.validate().is_err());
+	let starved: Limits = toml::from_str("tolerance = { per_second = 1, burst = 0.5 }").unwrap();
+	assert!(starved.validate().is_err());
+
+	let policy: Policy =
+		toml::from_str("half_life = 1000\nthresholds = [[3, \"Kick\"], [1, \"Warn\"]]").unwrap();
+	assert!(policy.validate().is_ok());
+	let mut watchlist = Watchlist::new(policy);
+	let offence = || Offence::DeadCommand {
+		message: "Spawn".to_string(),
+	};
+	assert_eq!(watchlist.report("p", offence(), 0), Some(Action::Warn));
+	watchlist.report("p", offence(), 0);
+	assert_eq!(watchlist.report("p", offence(), 0), Some(Action::Kick));
+	assert!(toml::from_str::<Policy>("thresholds = [[3, \"Jail\"]]").is_err());
+	let forgiving: Policy = toml::from_str("half_life = 0").unwrap();
+	assert!(forgiving.validate().is_err());
+}
@@ -0,0 +1,24 @@
+#![cfg(all(not(target_arch = "wasm32"), not(feature = "fixed-point")))]
+
+//the ships web.rs steps in the browser, stepped natively so a change to step that isn't pinned
+//again fails here too. Fixed point ships are encoded differently.
+
+mod fixtures;
+
+use base64::{engine::general_purpose, Engine};
+use fixtures::{PINNED, STEPS};
+use utils::map::{set_map, Map};
+use utils::trajectory::Trajectory;
+
+#[test]
+fn pinned_hashes_match() {
+	set_map(Map::from_toml(include_str!("../../maps/default.toml")).unwrap());
+	for (encoded, hash) in PINNED {
+		let mut testing: Trajectory =
+			bincode::deserialize(&general_purpose::STANDARD.decode(encoded).unwrap()).unwrap();
+		for _ in 1..STEPS {
+			testing.step();
+		}
+		assert_eq!(testing.hash_str(), hash, "for {}", encoded);
+	}
+}
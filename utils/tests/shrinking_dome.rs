@@ -0,0 +1,85 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+//a test binary of its own since it sets a different map than the other tests
+
+use utils::map::{set_map, Map};
+use utils::scalar::{Real, Scalar};
+use utils::trajectory::{Trajectory, Vector};
+
+const MAP: &str = r#"
+dome_radius = 6000.0
+bodies = []
+
+[dome_schedule]
+period = 10000
+stages = [
+	{ at = 0, radius = 6000.0 },
+	{ at = 2000, radius = 6000.0 },
+	{ at = 4000, radius = 2000.0 },
+]
+"#;
+
+fn ship(x: f32, vel: f32, time: u64) -> Trajectory {
+	Trajectory {
+		pos: Vector {
+			x: Scalar::from_f32(x),
+			y: Scalar::default(),
+		},
+		vel: Vector {
+			x: Scalar::from_f32(vel),
+			y: Scalar::default(),
+		},
+		time,
+		health: 100,
+		boosters: 1,
+		..Default::default()
+	}
+}
+
+#[test]
+fn dome_follows_its_schedule() {
+	let map = Map::from_toml(MAP).unwrap();
+	let radius = |time| map.dome_radius_at(time).to_f32();
+	assert_eq!(radius(1000), 6000.0);
+	assert!((radius(3000) - 4000.0).abs() < 0.01);
+	assert_eq!(radius(9999), 2000.0);
+	//and starts over every round
+	assert_eq!(radius(10000), 6000.0);
+	assert!((radius(1_700_000_003_000) - 4000.0).abs() < 0.01);
+
+	let invalid = [
+		MAP.replace("at = 0,", "at = 1,"),
+		MAP.replace("radius = 2000.0", "radius = 7000.0"),
+		MAP.replace("at = 4000", "at = 1000"),
+		MAP.replace("period = 10000", "period = 3000"),
+	];
+	for map in invalid {
+		assert!(Map::from_toml(&map).is_err());
+	}
+}
+
+#[test]
+fn ships_outside_the_dome_take_damage() {
+	set_map(Map::from_toml(MAP).unwrap());
+	//the dome has shrunk to 2000 by now
+	let mut returning = ship(3000.0, -100.0, 5000);
+	let mut fleeing = ship(3000.0, 100.0, 5000);
+	let mut safe = ship(1000.0, 0.0, 5000);
+	for trajectory in [&mut returning, &mut fleeing, &mut safe] {
+		trajectory.step();
+	}
+	assert!((returning.pos.x.to_f32() - 2990.0).abs() < 0.01);
+	assert!(fleeing.pos.x.to_f32() <= 3000.0);
+	assert!(fleeing.vel.x.to_f32() < 0.0);
+	assert_eq!(
+		(returning.health, fleeing.health, safe.health),
+		(98, 98, 100)
+	);
+
+	//inside ships bounce off the smaller dome like off the full one
+	let mut bouncing = ship(1950.0, 1000.0, 5000);
+	bouncing.step();
+	assert!((bouncing.pos.x.to_f32() - 2000.0).abs() < 0.01);
+	assert!(bouncing.vel.x.to_f32() < 0.0);
+	assert_eq!(bouncing.health, 100);
+}
@@ -0,0 +1,204 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+mod common;
+
+use common::{load_default_map, ship};
+use utils::history::TrajectoryHistory;
+use utils::scalar::Real;
+use utils::timeline::{Input, Timeline};
+use utils::trajectory::{Trajectory, UpdateType, UpdateTypeWrapper};
+
+#[test]
+fn history_rebuilds_past_states() {
+	load_default_map();
+	let mut ship = Trajectory {
+		propelling: true,
+		spin_direction: 1,
+		..ship(-1000.0, -2000.0)
+	};
+	let mut history = TrajectoryHistory::new(&ship);
+	let mut expected = Vec::new();
+	let mut changes = 1;
+	for step in 0..300u64 {
+		if step % 40 == 7 {
+			let change = UpdateTypeWrapper {
+				utype: [UpdateType::RotCcw, UpdateType::RotStop, UpdateType::RotCw]
+					[step as usize % 3],
+				value: None,
+			};
+			ship.apply_change(change.clone());
+			history.record(&ship, Some(change));
+			changes += 1;
+		}
+		expected.push(ship.clone());
+		ship.step();
+		history.checkpoint(&ship);
+	}
+	assert!(ship.health > 0);
+	//checkpoints are not changes
+	assert_eq!(history.revision(), changes);
+	for past in &expected[200..] {
+		//times between steps give the step before them
+		let rebuilt = history.at(past.time + 50).unwrap();
+		assert_eq!(rebuilt.hash_str(), past.hash_str());
+	}
+	//only the last ten seconds are kept
+	assert!(history.start().unwrap() <= ship.time - 10000);
+	assert!(history.start().unwrap() > 0);
+	assert!(history.at(0).is_none());
+
+	//a respawn earlier than the last snapshot replaces what came after it
+	let respawned = Trajectory {
+		time: ship.time - 150,
+		..expected[0].clone()
+	};
+	history.record(&respawned, None);
+	assert_eq!(history.at(ship.time).unwrap().pos.x.to_bits(), {
+		let mut stepped = respawned.clone();
+		stepped.step();
+		stepped.pos.x.to_bits()
+	});
+}
+
+#[test]
+fn interpolation_meets_every_step() {
+	load_default_map();
+	let mut ship = Trajectory {
+		propelling: true,
+		spin_direction: 1,
+		..ship(-1000.0, -2000.0)
+	};
+	let start = ship.clone();
+	for _ in 0..30 {
+		let before = ship.clone();
+		let drawn = before.interpolate(before.time);
+		assert_eq!(drawn.x.to_bits(), before.pos.x.to_f32().to_bits());
+		let middle = before.interpolate(before.time + 50);
+		ship.step();
+		//no jump when the next step is reached
+		let drawn = before.interpolate(ship.time);
+		assert_eq!(drawn.x.to_bits(), ship.pos.x.to_f32().to_bits());
+		assert_eq!(drawn.y.to_bits(), ship.pos.y.to_f32().to_bits());
+		//and halfway it is close to halfway, steps are short compared to the curves
+		let halfway = (
+			(before.pos.x.to_f32() + ship.pos.x.to_f32()) / 2.0,
+			(before.pos.y.to_f32() + ship.pos.y.to_f32()) / 2.0,
+		);
+		let off = ((middle.x - halfway.0).powi(2) + (middle.y - halfway.1).powi(2)).sqrt();
+		assert!(off < 5.0);
+	}
+	//far ahead it steps as often as needed first
+	let ahead = start.interpolate(start.time + 3000);
+	assert_eq!(ahead.x.to_bits(), ship.pos.x.to_f32().to_bits());
+	assert_eq!(ahead.r.to_bits(), ship.spin.to_f32().to_bits());
+}
+
+#[test]
+fn timeline_fits_in_the_past() {
+	load_default_map();
+	let start = Trajectory {
+		propelling: true,
+		..ship(-1000.0, -2000.0)
+	};
+	let turn = |utype| UpdateTypeWrapper { utype, value: None };
+	//what really happened: another client turned at 300 and stopped turning at 600
+	let mut truth = start.clone();
+	truth.advance_to_time(300);
+	let turned_at = truth.hash_str();
+	truth.apply_change(turn(UpdateType::RotCw));
+	truth.advance_to_time(600);
+	let stopped_at = truth.hash_str();
+	truth.apply_change(turn(UpdateType::RotStop));
+	truth.advance_to_time(1000);
+
+	//but here the second update came in time and the first one late
+	let mut current = start.clone();
+	let mut timeline = Timeline::new(&current);
+	let stop = Input {
+		time: 600,
+		hash: Some(stopped_at.clone()),
+		change: turn(UpdateType::RotStop),
+	};
+	timeline.insert(&mut current, stop).unwrap();
+	timeline.advance(&mut current, 500).unwrap();
+	let late = Input {
+		time: 300,
+		hash: Some(turned_at.clone()),
+		change: turn(UpdateType::RotCw),
+	};
+	//a late update that doesn't match is refused and changes nothing
+	let wrong = Input {
+		hash: Some(stopped_at),
+		..late.clone()
+	};
+	let before = current.hash_str();
+	assert!(timeline.insert(&mut current, wrong).is_err());
+	assert_eq!(current.hash_str(), before);
+	let drawn = current.interpolate(current.time);
+	timeline.insert(&mut current, late).unwrap();
+	assert_eq!(current.time, 500);
+	//the update still waiting is replayed as well
+	timeline.advance(&mut current, 1000).unwrap();
+	assert_eq!(current.hash_str(), truth.hash_str());
+
+	//the jump to the rebuilt path is eased out over a few frames
+	let mut rewound = start.clone();
+	let mut eased = Timeline::new(&rewound);
+	eased.advance(&mut rewound, 500).unwrap();
+	let late = Input {
+		time: 300,
+		hash: Some(turned_at),
+		change: turn(UpdateType::RotCw),
+	};
+	eased.insert(&mut rewound, late).unwrap();
+	let rebuilt = rewound.interpolate(500);
+	let shown = eased.smooth(rebuilt, 500);
+	assert_eq!(shown.x.to_bits(), drawn.x.to_bits());
+	assert_eq!(shown.y.to_bits(), drawn.y.to_bits());
+	let settled = eased.smooth(rebuilt, 1000);
+	assert_eq!(settled.x.to_bits(), rebuilt.x.to_bits());
+}
+
+#[test]
+fn corrections_keep_pending_changes() {
+	load_default_map();
+	let start = ship(-1000.0, -2000.0);
+	let mut current = start.clone();
+	let mut timeline = Timeline::new(&current);
+	timeline.advance(&mut current, 200).unwrap();
+	//a change this client made before hearing from the server
+	timeline.record(
+		&mut current,
+		UpdateTypeWrapper {
+			utype: UpdateType::PropOn,
+			value: None,
+		},
+	);
+	timeline.advance(&mut current, 800).unwrap();
+
+	//the server saw the ship get hit at 100, that is all it knows about yet
+	let mut authoritative = start.clone();
+	authoritative.advance_to_time(100);
+	authoritative.apply_change(UpdateTypeWrapper {
+		utype: UpdateType::Bullet,
+		value: Some(30),
+	});
+	timeline.correct(&mut current, &authoritative);
+
+	let mut later = authoritative.clone();
+	later.advance_to_time(200);
+	later.apply_change(UpdateTypeWrapper {
+		utype: UpdateType::PropOn,
+		value: None,
+	});
+	later.advance_to_time(600);
+	let mut expected = later.clone();
+	expected.advance_to_time(800);
+	assert_eq!(current.hash_str(), expected.hash_str());
+	assert_eq!(current.health, 70);
+	assert!(current.propelling);
+
+	//a correction that is newer than the change already includes it
+	timeline.correct(&mut current, &later);
+	assert_eq!(current.hash_str(), expected.hash_str());
+}